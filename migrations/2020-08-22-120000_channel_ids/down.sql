@@ -0,0 +1,4 @@
+ALTER TABLE dm_channel_users ALTER COLUMN id DROP DEFAULT;
+ALTER TABLE dm_channels ALTER COLUMN id DROP DEFAULT;
+ALTER TABLE room_channels ALTER COLUMN id DROP DEFAULT;
+ALTER TABLE channels ALTER COLUMN id DROP DEFAULT;
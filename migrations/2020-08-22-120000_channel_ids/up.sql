@@ -0,0 +1,4 @@
+ALTER TABLE channels ALTER COLUMN id SET DEFAULT id_generator();
+ALTER TABLE room_channels ALTER COLUMN id SET DEFAULT id_generator();
+ALTER TABLE dm_channels ALTER COLUMN id SET DEFAULT id_generator();
+ALTER TABLE dm_channel_users ALTER COLUMN id SET DEFAULT id_generator();
@@ -0,0 +1,2 @@
+DROP INDEX videos_room_id_position_idx;
+ALTER TABLE videos DROP COLUMN position;
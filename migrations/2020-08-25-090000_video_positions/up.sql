@@ -0,0 +1,11 @@
+ALTER TABLE videos ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+-- Keep current order (by creation time), leaving gaps between positions.
+UPDATE videos SET position = ordered.row_number * 1024
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY room_id ORDER BY created_at) AS row_number
+    FROM videos
+) AS ordered
+WHERE videos.id = ordered.id;
+
+CREATE INDEX videos_room_id_position_idx ON videos (room_id, position);
@@ -0,0 +1,3 @@
+ALTER TABLE videos DROP COLUMN codec;
+ALTER TABLE videos DROP COLUMN height;
+ALTER TABLE videos DROP COLUMN width;
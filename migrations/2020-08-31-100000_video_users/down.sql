@@ -0,0 +1,7 @@
+ALTER TABLE roles DROP COLUMN video_unlimited;
+
+ALTER TABLE rooms DROP COLUMN video_limit_duration;
+ALTER TABLE rooms DROP COLUMN video_limit_count;
+
+DROP INDEX videos_room_id_user_id_idx;
+ALTER TABLE videos DROP COLUMN user_id;
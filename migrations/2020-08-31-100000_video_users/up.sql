@@ -0,0 +1,11 @@
+ALTER TABLE videos ADD COLUMN user_id VARCHAR REFERENCES users(id) ON DELETE SET NULL;
+CREATE INDEX videos_room_id_user_id_idx ON videos (room_id, user_id);
+
+-- Queue limits per user, NULL means unlimited
+ALTER TABLE rooms ADD COLUMN video_limit_count INTEGER;
+-- In seconds
+ALTER TABLE rooms ADD COLUMN video_limit_duration INTEGER;
+
+ALTER TABLE roles ADD COLUMN video_unlimited INTEGER NOT NULL DEFAULT -1;
+UPDATE roles SET video_unlimited = 1 WHERE name IN ('Owner', 'Administator') AND is_default;
+UPDATE roles SET video_unlimited = 0 WHERE name = 'Everyone' AND is_default;
@@ -0,0 +1,2 @@
+-- Percentage of present viewers, required to skip current video
+ALTER TABLE rooms ADD COLUMN skip_threshold INTEGER NOT NULL DEFAULT 50;
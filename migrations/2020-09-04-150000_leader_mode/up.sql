@@ -0,0 +1,2 @@
+-- Only the leader controls the player
+ALTER TABLE rooms ADD COLUMN is_leader_mode BOOLEAN NOT NULL DEFAULT 'f';
@@ -0,0 +1,2 @@
+ALTER TABLE rooms DROP COLUMN is_shuffle;
+ALTER TABLE rooms DROP COLUMN repeat_mode;
@@ -0,0 +1,3 @@
+-- 0 - off, 1 - repeat all, 2 - repeat one
+ALTER TABLE rooms ADD COLUMN repeat_mode INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE rooms ADD COLUMN is_shuffle BOOLEAN NOT NULL DEFAULT 'f';
@@ -0,0 +1,9 @@
+-- Last known player state of a room, restored once server restarts
+CREATE TABLE IF NOT EXISTS playbacks (
+    room_id VARCHAR NOT NULL PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    video_id VARCHAR NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    -- In milliseconds
+    position BIGINT NOT NULL DEFAULT 0,
+    is_paused BOOLEAN NOT NULL DEFAULT 'f',
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,5 @@
+UPDATE videos SET subtitles_id = NULL
+    WHERE subtitles_id NOT IN (SELECT id FROM files);
+ALTER TABLE videos DROP CONSTRAINT IF EXISTS videos_subtitles_id_fkey;
+ALTER TABLE videos ADD CONSTRAINT videos_subtitles_id_fkey
+    FOREIGN KEY (subtitles_id) REFERENCES files(id) ON DELETE SET NULL;
@@ -0,0 +1,6 @@
+-- `videos.subtitles_id` was pointing at `files` instead of `subtitles`
+UPDATE videos SET subtitles_id = NULL
+    WHERE subtitles_id NOT IN (SELECT id FROM subtitles);
+ALTER TABLE videos DROP CONSTRAINT IF EXISTS videos_subtitles_id_fkey;
+ALTER TABLE videos ADD CONSTRAINT videos_subtitles_id_fkey
+    FOREIGN KEY (subtitles_id) REFERENCES subtitles(id) ON DELETE SET NULL;
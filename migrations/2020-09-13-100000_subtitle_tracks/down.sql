@@ -0,0 +1,12 @@
+ALTER TABLE videos ADD COLUMN subtitles_id VARCHAR REFERENCES subtitles(id) ON DELETE SET NULL;
+UPDATE videos SET subtitles_id = subtitles.id FROM subtitles
+    WHERE subtitles.video_id = videos.id AND subtitles.is_default;
+
+ALTER TABLE subtitles DROP COLUMN created_at;
+ALTER TABLE subtitles DROP COLUMN time_offset;
+ALTER TABLE subtitles DROP COLUMN is_default;
+ALTER TABLE subtitles DROP COLUMN label;
+ALTER TABLE subtitles DROP COLUMN language;
+
+DROP INDEX IF EXISTS subtitles_video_id_idx;
+ALTER TABLE subtitles DROP COLUMN video_id;
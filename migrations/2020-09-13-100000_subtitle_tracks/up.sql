@@ -0,0 +1,16 @@
+-- Video may have several subtitle tracks, so tracks point at their video
+ALTER TABLE subtitles ADD COLUMN video_id VARCHAR REFERENCES videos(id) ON DELETE CASCADE;
+UPDATE subtitles SET video_id = videos.id FROM videos WHERE videos.subtitles_id = subtitles.id;
+DELETE FROM subtitles WHERE video_id IS NULL;
+ALTER TABLE subtitles ALTER COLUMN video_id SET NOT NULL;
+CREATE INDEX subtitles_video_id_idx ON subtitles (video_id);
+
+ALTER TABLE subtitles ADD COLUMN language VARCHAR;
+ALTER TABLE subtitles ADD COLUMN label VARCHAR;
+ALTER TABLE subtitles ADD COLUMN is_default BOOLEAN NOT NULL DEFAULT 'f';
+-- In milliseconds, added to cue timings by clients
+ALTER TABLE subtitles ADD COLUMN time_offset INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE subtitles ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT NOW();
+UPDATE subtitles SET is_default = 't';
+
+ALTER TABLE videos DROP COLUMN subtitles_id;
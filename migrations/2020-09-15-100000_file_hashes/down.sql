@@ -0,0 +1 @@
+DROP INDEX IF EXISTS files_hash_ext_idx;
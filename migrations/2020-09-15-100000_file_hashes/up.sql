@@ -0,0 +1,2 @@
+-- Files are looked up by content hash, so the same content is stored once
+CREATE INDEX files_hash_ext_idx ON files (hash, ext);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS emotes_room_id_name_idx;
+ALTER TABLE emotes ALTER COLUMN id DROP DEFAULT;
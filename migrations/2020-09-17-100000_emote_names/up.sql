@@ -0,0 +1,4 @@
+-- Emotes are created by the server, so their ids are generated like everywhere else
+ALTER TABLE emotes ALTER COLUMN id SET DEFAULT id_generator();
+-- Emotes are used by name, so names are unique among emotes of the room, except deleted ones
+CREATE UNIQUE INDEX emotes_room_id_name_idx ON emotes (room_id, name) WHERE NOT is_deleted;
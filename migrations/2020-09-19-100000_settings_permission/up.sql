@@ -0,0 +1,4 @@
+-- Permission to change playback settings of the room: queue limits, skip threshold and leader mode.
+-- Roles keep what they could do before, when these settings were covered by `public_update`.
+ALTER TABLE roles ADD COLUMN settings_update INTEGER NOT NULL DEFAULT -1;
+UPDATE roles SET settings_update = public_update;
@@ -0,0 +1,4 @@
+-- When the content was last saved. Files, reused by new uploads of the same content,
+-- get a fresh grace period before they are collected as garbage
+ALTER TABLE files ADD COLUMN saved_at TIMESTAMP NOT NULL DEFAULT now();
+UPDATE files SET saved_at = created_at;
@@ -137,6 +137,21 @@ impl RoomChannel {
             .map_err(From::from)
     }
 
+    /// Get primary channel of the room, creating one if room has none yet.
+    pub fn get_or_create(
+        room_id_query: String,
+        conn: &PgConnection,
+    ) -> Result<RoomChannel, DieselError> {
+        match RoomChannel::by_room_id(room_id_query.clone(), conn) {
+            Err(ref err) if super::helpers::is_not_found_error(err) => NewRoomChannel {
+                channel_id: None,
+                room_id: room_id_query,
+            }
+            .create(conn),
+            result => result,
+        }
+    }
+
     pub fn by_id(room_channel_id: String, conn: &PgConnection) -> Result<RoomChannel, DieselError> {
         use crate::schema::room_channels::dsl::*;
 
@@ -24,7 +24,6 @@ pub struct Emote {
     #[serde(skip_serializing)]
     pub is_deleted: bool,
 
-    #[serde(skip_serializing)]
     pub deleted_at: Option<NaiveDateTime>,
 
     #[serde(skip_serializing)]
@@ -33,6 +32,7 @@ pub struct Emote {
 
 impl Emote {
     // TODO: paginations
+    /// Emotes of the room, which are not deleted.
     pub fn list_by_room_id(
         room_id_query: String,
         conn: &PgConnection,
@@ -41,6 +41,8 @@ impl Emote {
 
         emotes
             .filter(room_id.eq(room_id_query.clone()))
+            .filter(is_deleted.eq(false))
+            .order(name.asc())
             .load::<Emote>(conn)
             .map_err(|err| {
                 error!(
@@ -52,6 +54,52 @@ impl Emote {
             .map_err(From::from)
     }
 
+    /// Deleted emotes of the room, which can be restored. Recently deleted go first.
+    pub fn list_deleted_by_room_id(
+        room_id_query: String,
+        conn: &PgConnection,
+    ) -> Result<Vec<Emote>, DieselError> {
+        use crate::schema::emotes::dsl::*;
+
+        emotes
+            .filter(room_id.eq(room_id_query.clone()))
+            .filter(is_deleted.eq(true))
+            .order(deleted_at.desc())
+            .load::<Emote>(conn)
+            .map_err(|err| {
+                error!(
+                    "Couldn't query deleted emotes by room id {:?}: {}",
+                    room_id_query, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+
+    /// Emote of the room with `name_query`, which is not deleted.
+    pub fn by_name(
+        room_id_query: String,
+        name_query: &str,
+        conn: &PgConnection,
+    ) -> Result<Option<Emote>, DieselError> {
+        use crate::schema::emotes::dsl::*;
+
+        emotes
+            .filter(room_id.eq(room_id_query.clone()))
+            .filter(name.eq(name_query))
+            .filter(is_deleted.eq(false))
+            .first::<Emote>(conn)
+            .optional()
+            .map_err(|err| {
+                error!(
+                    "Couldn't query emote {:?} of room {:?}: {}",
+                    name_query, room_id_query, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+
     pub fn by_id(emote_id: String, conn: &PgConnection) -> Result<Emote, DieselError> {
         use crate::schema::emotes::dsl::*;
 
@@ -80,7 +128,7 @@ impl Emote {
     pub fn update(&self, conn: &PgConnection) -> Result<Emote, DieselError> {
         use crate::schema::emotes::dsl::*;
 
-        diesel::update(emotes)
+        diesel::update(emotes.filter(id.eq(self.id.clone())))
             .set(self)
             .get_result::<Emote>(conn)
             .map_err(|err| {
@@ -89,6 +137,36 @@ impl Emote {
             })
             .map_err(From::from)
     }
+
+    /// Mark emote as deleted. Its file is kept, so emote can be restored.
+    pub fn soft_delete(&self, conn: &PgConnection) -> Result<Emote, DieselError> {
+        use crate::schema::emotes::dsl::*;
+
+        diesel::update(emotes.filter(id.eq(self.id.clone())))
+            .set((
+                is_deleted.eq(true),
+                deleted_at.eq(diesel::dsl::now.nullable()),
+            ))
+            .get_result::<Emote>(conn)
+            .map_err(|err| {
+                error!("Couldn't delete emote {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    pub fn restore(&self, conn: &PgConnection) -> Result<Emote, DieselError> {
+        use crate::schema::emotes::dsl::*;
+
+        diesel::update(emotes.filter(id.eq(self.id.clone())))
+            .set((is_deleted.eq(false), deleted_at.eq(None::<NaiveDateTime>)))
+            .get_result::<Emote>(conn)
+            .map_err(|err| {
+                error!("Couldn't restore emote {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
 }
 
 #[derive(Insertable, AsExpression, Debug, Associations, Deserialize, Serialize)]
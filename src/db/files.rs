@@ -5,6 +5,8 @@ use crate::diesel::prelude::*;
 use crate::diesel::*;
 
 use chrono::NaiveDateTime;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
 use serde::{Deserialize, Serialize};
 
 #[derive(AsChangeset, Associations, Queryable, Debug, Identifiable, Serialize, Clone)]
@@ -14,6 +16,8 @@ pub struct File {
     pub hash: String,
     pub ext: String,
     pub created_at: NaiveDateTime,
+    /// Last time the same content was saved, which may be later than `created_at`.
+    pub saved_at: NaiveDateTime,
 }
 
 impl File {
@@ -30,6 +34,126 @@ impl File {
             .map_err(From::from)
     }
 
+    /// Any file with the given content. `None` if there is no such file yet.
+    pub fn by_hash(
+        hash_query: &str,
+        ext_query: &str,
+        conn: &PgConnection,
+    ) -> Result<Option<File>, DieselError> {
+        use crate::schema::files::dsl::*;
+
+        files
+            .filter(hash.eq(hash_query))
+            .filter(ext.eq(ext_query))
+            .order(created_at.asc())
+            .first::<File>(conn)
+            .optional()
+            .map_err(|err| {
+                error!("Couldn't query file by hash {:?}: {}", hash_query, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    /// Lock the content of file until the lock is dropped.
+    ///
+    /// Saving and collecting the same content are serialized by it, so stored content
+    /// isn't deleted while it's being saved again.
+    pub fn lock_content<'a>(
+        hash_query: &str,
+        conn: &'a PgConnection,
+    ) -> Result<ContentLock<'a>, DieselError> {
+        // Advisory lock key is a 64-bit number, which is taken from the hash itself.
+        let key = hash_query
+            .get(..16)
+            .and_then(|prefix| u64::from_str_radix(prefix, 16).ok())
+            .unwrap_or_default() as i64;
+
+        sql_query("SELECT pg_advisory_lock($1)")
+            .bind::<BigInt, _>(key)
+            .execute(conn)
+            .map_err(|err| {
+                error!("Couldn't lock content {:?}: {}", hash_query, err);
+                err
+            })?;
+
+        Ok(ContentLock { key, conn })
+    }
+
+    /// Mark the content as saved right now.
+    pub fn mark_saved(&self, conn: &PgConnection) -> Result<File, DieselError> {
+        use crate::schema::files::dsl::*;
+
+        diesel::update(files.filter(id.eq(self.id.to_owned())))
+            .set(saved_at.eq(diesel::dsl::now))
+            .get_result::<File>(conn)
+            .map_err(|err| {
+                error!("Couldn't mark file {:?} as saved: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    /// Number of files with the given content.
+    pub fn count_by_hash(
+        hash_query: &str,
+        ext_query: &str,
+        conn: &PgConnection,
+    ) -> Result<i64, DieselError> {
+        use crate::schema::files::dsl::*;
+
+        files
+            .filter(hash.eq(hash_query))
+            .filter(ext.eq(ext_query))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|err| {
+                error!("Couldn't count files by hash {:?}: {}", hash_query, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    /// Files saved before `saved_before`, which are referenced by nothing.
+    ///
+    /// Only files of `content`, i.e. hash and extension, are listed if it's given.
+    pub fn list_orphaned(
+        saved_before: NaiveDateTime,
+        content: Option<(&str, &str)>,
+        conn: &PgConnection,
+    ) -> Result<Vec<File>, DieselError> {
+        use crate::schema::{emotes, subtitles, users, videos};
+        use diesel::dsl::{exists, not};
+
+        let mut query = files::table.into_boxed();
+        if let Some((hash_query, ext_query)) = content {
+            query = query
+                .filter(files::hash.eq(hash_query))
+                .filter(files::ext.eq(ext_query));
+        }
+
+        query
+            .filter(files::saved_at.lt(saved_before))
+            .filter(not(exists(
+                users::table.filter(users::file_id.eq(files::id.nullable())),
+            )))
+            .filter(not(exists(
+                videos::table.filter(videos::file_id.eq(files::id.nullable())),
+            )))
+            .filter(not(exists(
+                subtitles::table.filter(subtitles::file_id.eq(files::id)),
+            )))
+            .filter(not(exists(
+                emotes::table.filter(emotes::file_id.eq(files::id)),
+            )))
+            .load::<File>(conn)
+            .map_err(|err| {
+                error!("Couldn't query orphaned files: {}", err);
+                err
+            })
+            .map_err(From::from)
+    }
+
     pub fn delete(&self, conn: &PgConnection) -> Result<usize, DieselError> {
         use crate::schema::files::dsl::*;
 
@@ -43,6 +167,24 @@ impl File {
     }
 }
 
+/// Lock on stored content, see `File::lock_content`. Released once dropped.
+pub struct ContentLock<'a> {
+    key: i64,
+    conn: &'a PgConnection,
+}
+
+impl<'a> Drop for ContentLock<'a> {
+    fn drop(&mut self) {
+        let unlocked = sql_query("SELECT pg_advisory_unlock($1)")
+            .bind::<BigInt, _>(self.key)
+            .execute(self.conn);
+
+        if let Err(err) = unlocked {
+            error!("Couldn't unlock content {:?}: {}", self.key, err);
+        }
+    }
+}
+
 #[derive(Insertable, AsChangeset, AsExpression, Debug, Associations, Deserialize, Serialize)]
 #[table_name = "files"]
 // We only need camelCase for consistent debug output
@@ -43,6 +43,29 @@ pub fn get_highest_user_role(
     Ok(first)
 }
 
+/// Get timeout between messages in seconds.
+///
+/// Timeout is taken from the highest user role.
+/// If it is unset (negative), it's inherited from the next role which has it set.
+pub fn get_user_message_timeout(
+    user_id: Option<String>,
+    room_id: String,
+    conn: &PgConnection,
+) -> Result<i32, DieselError> {
+    let highest_role = get_highest_user_role(user_id.clone(), room_id.clone(), conn)?;
+    if highest_role.message_timeout >= 0 {
+        return Ok(highest_role.message_timeout);
+    }
+
+    let timeout = list_user_roles_in_room(user_id, room_id, conn)?
+        .iter()
+        .map(|role| role.message_timeout)
+        .find(|timeout| *timeout >= 0)
+        .unwrap_or(0);
+
+    Ok(timeout)
+}
+
 /// user_id is None if user is anonymous
 pub fn list_user_roles_in_room(
     user_id: Option<String>,
@@ -69,6 +69,35 @@ impl Message {
         })
     }
 
+    /// List last `limit` messages of a channel along with their authors, oldest first.
+    pub fn list_last_by_channel_id(
+        message_channel_id: String,
+        limit: i64,
+        conn: &PgConnection,
+    ) -> Result<Vec<(Message, User)>, DieselError> {
+        use crate::schema::messages::dsl::*;
+        use crate::schema::users;
+
+        messages
+            .inner_join(users::table)
+            .filter(channel_id.eq(message_channel_id.clone()))
+            .order(created_at.desc())
+            .limit(limit)
+            .load::<(Message, User)>(conn)
+            .map(|mut list| {
+                list.reverse();
+                list
+            })
+            .map_err(|err| {
+                error!(
+                    "Couldn't query last messages by channel_id {:?}: {}",
+                    message_channel_id, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+
     pub fn delete(&self, conn: &PgConnection) -> Result<usize, DieselError> {
         use crate::schema::messages::dsl::*;
 
@@ -9,6 +9,7 @@ mod errors;
 mod files;
 pub mod helpers;
 mod messages;
+mod playbacks;
 mod restrains;
 mod roles;
 mod rooms;
@@ -22,6 +23,7 @@ pub use emotes::*;
 pub use errors::*;
 pub use files::*;
 pub use messages::*;
+pub use playbacks::*;
 pub use restrains::*;
 pub use roles::*;
 pub use rooms::*;
@@ -29,6 +31,8 @@ pub use users::*;
 pub use videos::*;
 
 pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
+pub type DbConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+
 pub fn get_pool() -> DbPool {
     let db_url = env::DATABASE_URL.clone();
     let manager = ConnectionManager::<PgConnection>::new(db_url);
@@ -0,0 +1,95 @@
+use super::DieselError;
+use crate::schema::playbacks;
+
+use crate::diesel::prelude::*;
+use crate::diesel::*;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Checkpoint of room's player, so playback survives server restarts.
+///
+/// Checkpoints are read lazily, once the room's hub is started by the first connection
+/// after restart. Rooms, which nobody joins, don't play on with no one watching.
+#[derive(Associations, Queryable, Debug, Identifiable, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+#[primary_key(room_id)]
+pub struct Playback {
+    pub room_id: String,
+    pub video_id: String,
+    /// position in milliseconds at the moment of `updated_at`.
+    pub position: i64,
+    pub is_paused: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Playback {
+    /// `None` if the room has no checkpoint.
+    pub fn by_room_id(
+        room_id_query: String,
+        conn: &PgConnection,
+    ) -> Result<Option<Playback>, DieselError> {
+        use crate::schema::playbacks::dsl::*;
+
+        playbacks
+            .filter(room_id.eq(room_id_query.clone()))
+            .first::<Playback>(conn)
+            .optional()
+            .map_err(|err| {
+                error!(
+                    "Couldn't query playback by room id {:?}: {}",
+                    room_id_query, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+
+    pub fn delete_by_room_id(
+        room_id_query: String,
+        conn: &PgConnection,
+    ) -> Result<usize, DieselError> {
+        use crate::schema::playbacks::dsl::*;
+
+        diesel::delete(playbacks.filter(room_id.eq(room_id_query.clone())))
+            .execute(conn)
+            .map_err(|err| {
+                error!(
+                    "Couldn't delete playback of room {:?}: {}",
+                    room_id_query, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+}
+
+#[derive(Insertable, AsChangeset, Debug, Deserialize, Serialize)]
+#[table_name = "playbacks"]
+#[serde(rename_all = "camelCase")]
+pub struct NewPlayback {
+    pub room_id: String,
+    pub video_id: String,
+    pub position: i64,
+    pub is_paused: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+impl NewPlayback {
+    /// Create playback or overwrite existing one of the room.
+    pub fn save(&self, conn: &PgConnection) -> Result<Playback, DieselError> {
+        use crate::schema::playbacks::dsl::*;
+
+        diesel::insert_into(playbacks)
+            .values(self)
+            .on_conflict(room_id)
+            .do_update()
+            .set(self)
+            .get_result::<Playback>(conn)
+            .map_err(|err| {
+                error!("Couldn't save playback {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+}
@@ -91,6 +91,8 @@ pub struct Role {
     pub path_update: PermissionState,
     /// permission to update room's visibility
     pub public_update: PermissionState,
+    /// permission to update room's playback settings, like queue limits and leader mode
+    pub settings_update: PermissionState,
     /// permission to update delete room
     pub room_delete: PermissionState,
     /// permission to update enter room
@@ -141,6 +143,8 @@ pub struct Role {
     pub video_iframe: PermissionState,
     /// permission to add video by direct link
     pub video_raw: PermissionState,
+    /// permission to bypass room's queue limits
+    pub video_unlimited: PermissionState,
 
     /// permission to pause playlist
     pub player_pause: PermissionState,
@@ -319,6 +323,7 @@ pub struct NewRole {
     pub title_update: PermissionState,
     pub path_update: PermissionState,
     pub public_update: PermissionState,
+    pub settings_update: PermissionState,
     pub room_delete: PermissionState,
     pub room_view: PermissionState,
     pub audit_log_read: PermissionState,
@@ -342,6 +347,7 @@ pub struct NewRole {
     pub video_move: PermissionState,
     pub video_iframe: PermissionState,
     pub video_raw: PermissionState,
+    pub video_unlimited: PermissionState,
     pub player_pause: PermissionState,
     pub player_resume: PermissionState,
     pub player_rewind: PermissionState,
@@ -371,6 +377,7 @@ impl Default for NewRole {
             title_update: PermissionState::Unset,
             path_update: PermissionState::Unset,
             public_update: PermissionState::Unset,
+            settings_update: PermissionState::Unset,
             room_delete: PermissionState::Unset,
             room_view: PermissionState::Unset,
             audit_log_read: PermissionState::Unset,
@@ -394,6 +401,7 @@ impl Default for NewRole {
             video_move: PermissionState::Unset,
             video_iframe: PermissionState::Unset,
             video_raw: PermissionState::Unset,
+            video_unlimited: PermissionState::Unset,
             player_pause: PermissionState::Unset,
             player_resume: PermissionState::Unset,
             player_rewind: PermissionState::Unset,
@@ -434,6 +442,7 @@ impl NewRole {
             title_update: PermissionState::Allowed,
             path_update: PermissionState::Allowed,
             public_update: PermissionState::Allowed,
+            settings_update: PermissionState::Allowed,
             room_delete: PermissionState::Allowed,
             room_view: PermissionState::Allowed,
             audit_log_read: PermissionState::Allowed,
@@ -457,6 +466,7 @@ impl NewRole {
             video_move: PermissionState::Allowed,
             video_iframe: PermissionState::Allowed,
             video_raw: PermissionState::Allowed,
+            video_unlimited: PermissionState::Allowed,
             player_pause: PermissionState::Allowed,
             player_resume: PermissionState::Allowed,
             player_rewind: PermissionState::Allowed,
@@ -538,6 +548,7 @@ impl NewRole {
             title_update: PermissionState::Forbidden,
             path_update: PermissionState::Forbidden,
             public_update: PermissionState::Forbidden,
+            settings_update: PermissionState::Forbidden,
             room_delete: PermissionState::Forbidden,
             room_view: PermissionState::Allowed,
             audit_log_read: PermissionState::Forbidden,
@@ -561,6 +572,7 @@ impl NewRole {
             video_move: PermissionState::Forbidden,
             video_iframe: PermissionState::Forbidden,
             video_raw: PermissionState::Forbidden,
+            video_unlimited: PermissionState::Forbidden,
             player_pause: PermissionState::Forbidden,
             player_resume: PermissionState::Forbidden,
             player_rewind: PermissionState::Forbidden,
@@ -1,11 +1,59 @@
 use super::DieselError;
 use crate::schema::rooms;
+use diesel::backend::Backend;
+use diesel::deserialize::FromSql;
+use diesel::serialize::ToSql;
+use std::io::Write;
 
 use crate::diesel::prelude::*;
 use crate::diesel::*;
 
 use chrono::NaiveDateTime;
+use diesel::sql_types::*;
 use serde::{Deserialize, Serialize};
+use serde_repr::*;
+
+/// What happens to a video, once it's played.
+#[derive(
+    Debug, Copy, Clone, PartialEq, AsExpression, FromSqlRow, Serialize_repr, Deserialize_repr,
+)]
+#[sql_type = "Integer"]
+#[repr(i32)]
+pub enum RepeatMode {
+    /// Video is removed from playlist.
+    Off = 0,
+    /// Video is moved to the end of playlist.
+    All = 1,
+    /// Video is played again.
+    One = 2,
+}
+
+impl From<i32> for RepeatMode {
+    fn from(value: i32) -> RepeatMode {
+        match value {
+            1 => RepeatMode::All,
+            2 => RepeatMode::One,
+            _ => RepeatMode::Off,
+        }
+    }
+}
+impl<ST, DB> FromSql<ST, DB> for RepeatMode
+where
+    i32: FromSql<ST, DB>,
+    DB: Backend,
+{
+    fn from_sql(value: Option<&<DB as Backend>::RawValue>) -> deserialize::Result<Self> {
+        <i32 as FromSql<ST, DB>>::from_sql(value).map(RepeatMode::from)
+    }
+}
+impl<DB> ToSql<Integer, DB> for RepeatMode
+where
+    DB: Backend,
+{
+    fn to_sql<W: Write>(&self, out: &mut serialize::Output<W, DB>) -> serialize::Result {
+        <i32 as ToSql<Integer, DB>>::to_sql(&(*self as i32), out)
+    }
+}
 
 #[derive(AsChangeset, Associations, Queryable, Debug, Identifiable, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -31,6 +79,25 @@ pub struct Room {
 
     #[serde(skip_serializing)]
     pub deleted_at: Option<NaiveDateTime>,
+
+    /// max number of videos queued by a single user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_limit_count: Option<i32>,
+
+    /// max total duration of videos queued by a single user, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_limit_duration: Option<i32>,
+
+    /// percentage of present viewers, who should vote to skip current video.
+    pub skip_threshold: i32,
+
+    /// only the leader, chosen among present users, controls the player.
+    pub is_leader_mode: bool,
+
+    pub repeat_mode: RepeatMode,
+
+    /// next video is picked at random.
+    pub is_shuffle: bool,
 }
 
 impl Room {
@@ -88,6 +155,80 @@ impl Room {
             .map_err(From::from)
     }
 
+    /// Set per user queue limits, `None` lifts the limit.
+    pub fn update_video_limits(
+        &self,
+        limit_count: Option<i32>,
+        limit_duration: Option<i32>,
+        conn: &PgConnection,
+    ) -> Result<Room, DieselError> {
+        use crate::schema::rooms::dsl::*;
+
+        diesel::update(rooms.filter(id.eq(self.id.clone())))
+            .set((
+                video_limit_count.eq(limit_count),
+                video_limit_duration.eq(limit_duration),
+            ))
+            .get_result::<Room>(conn)
+            .map_err(|err| {
+                error!("Couldn't update video limits of room {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    pub fn update_skip_threshold(
+        &self,
+        threshold: i32,
+        conn: &PgConnection,
+    ) -> Result<Room, DieselError> {
+        use crate::schema::rooms::dsl::*;
+
+        diesel::update(rooms.filter(id.eq(self.id.clone())))
+            .set(skip_threshold.eq(threshold))
+            .get_result::<Room>(conn)
+            .map_err(|err| {
+                error!("Couldn't update skip threshold of room {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    pub fn update_leader_mode(
+        &self,
+        leader_mode: bool,
+        conn: &PgConnection,
+    ) -> Result<Room, DieselError> {
+        use crate::schema::rooms::dsl::*;
+
+        diesel::update(rooms.filter(id.eq(self.id.clone())))
+            .set(is_leader_mode.eq(leader_mode))
+            .get_result::<Room>(conn)
+            .map_err(|err| {
+                error!("Couldn't update leader mode of room {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
+    pub fn update_queue_mode(
+        &self,
+        repeat: RepeatMode,
+        shuffle: bool,
+        conn: &PgConnection,
+    ) -> Result<Room, DieselError> {
+        use crate::schema::rooms::dsl::*;
+
+        diesel::update(rooms.filter(id.eq(self.id.clone())))
+            .set((repeat_mode.eq(repeat), is_shuffle.eq(shuffle)))
+            .get_result::<Room>(conn)
+            .map_err(|err| {
+                error!("Couldn't update queue mode of room {:?}: {}", self, err);
+                err
+            })
+            .map_err(From::from)
+    }
+
     pub fn update(&self, conn: &PgConnection) -> Result<Room, DieselError> {
         use crate::schema::rooms::dsl::*;
 
@@ -118,6 +118,31 @@ impl User {
     }
 }
 
+/// User as seen by other users. Private fields, like email, are never shown to anyone else.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicUser {
+    pub id: String,
+    pub username: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl From<&User> for PublicUser {
+    fn from(user: &User) -> PublicUser {
+        PublicUser {
+            id: user.id.clone(),
+            username: user.username.clone(),
+            nickname: user.nickname.clone(),
+            color: user.color.clone(),
+        }
+    }
+}
+
 #[derive(AsChangeset, AsExpression, Insertable, Debug, Associations, Deserialize, Serialize)]
 #[table_name = "users"]
 // We only need camelCase for consistent debug output
@@ -1,4 +1,5 @@
 use super::DieselError;
+use crate::schema::rooms;
 use crate::schema::subtitles;
 use crate::schema::videos;
 
@@ -9,15 +10,66 @@ use std::vec::Vec;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
+/// Gap left between positions of adjacent videos,
+/// so video can be moved without touching the rest of playlist.
+const POSITION_STEP: i32 = 1024;
+
+/// Where to move a video.
+///
+/// Serialized as `"front"`, `"next"` or `{ "index": 3 }`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum MoveTarget {
+    /// Start of the playlist.
+    Front,
+    /// Right after the video, which is playing now.
+    Next,
+    Index(usize),
+}
+
+/// Spread positions evenly, in the order of `list`.
+fn renumber(list: &mut [Video], conn: &PgConnection) -> Result<(), DieselError> {
+    use crate::schema::videos::dsl::*;
+
+    for (i, video) in list.iter_mut().enumerate() {
+        video.position = (i as i32 + 1) * POSITION_STEP;
+        diesel::update(videos.filter(id.eq(video.id.clone())))
+            .set(position.eq(video.position))
+            .execute(conn)
+            .map_err(|err| {
+                error!("Couldn't renumber video {:?}: {}", video, err);
+                err
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Lock room row until the end of transaction,
+/// so concurrent playlist edits of the same room are serialized.
+pub fn lock_playlist(room_id_query: &str, conn: &PgConnection) -> Result<(), DieselError> {
+    rooms::table
+        .find(room_id_query)
+        .select(rooms::id)
+        .for_update()
+        .first::<String>(conn)
+        .map(|_| ())
+        .map_err(|err| {
+            error!(
+                "Couldn't lock playlist of room {:?}: {}",
+                room_id_query, err
+            );
+            err
+        })
+        .map_err(From::from)
+}
+
 #[derive(AsChangeset, Associations, Queryable, Debug, Identifiable, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Video {
     pub id: String,
     pub room_id: String,
 
-    #[serde(skip_serializing)]
-    pub subtitles_id: Option<String>,
-
     #[serde(skip_serializing)]
     pub file_id: Option<String>,
 
@@ -34,6 +86,36 @@ pub struct Video {
     pub is_iframe: bool,
     pub is_live: bool,
     pub created_at: NaiveDateTime,
+
+    /// position in playlist. lower number - closer to the start.
+    /// Positions are not contiguous.
+    #[serde(skip_serializing)]
+    pub position: i32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+
+    /// bitrates of adaptive stream's variants, in bits per second.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrates: Option<Vec<i32>>,
+
+    /// user who added the video. `None` for anonymous users and deleted accounts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Videos queued by a single user.
+#[derive(Debug, Clone, Default)]
+pub struct QueueStats {
+    pub count: i64,
+    /// Sum of known durations, in seconds.
+    pub duration: i64,
 }
 
 impl Video {
@@ -45,6 +127,7 @@ impl Video {
 
         videos
             .filter(room_id.eq(room_id_query.clone()))
+            .order((position.asc(), created_at.asc()))
             .load::<Video>(conn)
             .map_err(|err| {
                 error!(
@@ -56,6 +139,40 @@ impl Video {
             .map_err(From::from)
     }
 
+    /// Count videos in room's playlist, added by user. `None` stands for anonymous users.
+    pub fn queue_stats(
+        room_id_query: String,
+        user_id_query: Option<String>,
+        conn: &PgConnection,
+    ) -> Result<QueueStats, DieselError> {
+        use crate::schema::videos::dsl::*;
+        // `IS NOT DISTINCT FROM` matches NULL too.
+        let query = videos
+            .filter(room_id.eq(room_id_query.clone()))
+            .filter(user_id.is_not_distinct_from(user_id_query.clone()));
+
+        let count = query.clone().count().get_result::<i64>(conn);
+        let total_duration = query
+            .select(diesel::dsl::sum(duration))
+            .first::<Option<i64>>(conn);
+
+        count
+            .and_then(|count| {
+                Ok(QueueStats {
+                    count,
+                    duration: total_duration?.unwrap_or(0),
+                })
+            })
+            .map_err(|err| {
+                error!(
+                    "Couldn't count videos of user {:?} in room {:?}: {}",
+                    user_id_query, room_id_query, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+
     pub fn by_id(video_id: String, conn: &PgConnection) -> Result<Video, DieselError> {
         use crate::schema::videos::dsl::*;
 
@@ -89,6 +206,103 @@ impl Video {
             .map_err(From::from)
     }
 
+    /// Move video to `target` in its room's playlist.
+    ///
+    /// `playing_id` is id of the video playing now, if any.
+    /// Returns reordered playlist.
+    pub fn move_to(
+        &self,
+        target: MoveTarget,
+        playing_id: Option<&str>,
+        conn: &PgConnection,
+    ) -> Result<Vec<Video>, DieselError> {
+        use crate::schema::videos::dsl::*;
+
+        conn.transaction(|| {
+            lock_playlist(&self.room_id, conn)?;
+
+            let mut list = Video::list_by_room_id(self.room_id.clone(), conn)?;
+            let current = match list.iter().position(|v| v.id == self.id) {
+                Some(current) => current,
+                None => return Err(diesel::result::Error::NotFound.into()),
+            };
+            let video = list.remove(current);
+
+            let index = match target {
+                MoveTarget::Front => 0,
+                MoveTarget::Index(index) => index.min(list.len()),
+                // Position of the playing video, once moved video is taken out of playlist.
+                MoveTarget::Next => playing_id
+                    .and_then(|playing_id| list.iter().position(|v| v.id == playing_id))
+                    .map_or(0, |playing| playing + 1),
+            };
+            let prev = index.checked_sub(1).map(|i| list[i].position);
+            let next = list.get(index).map(|v| v.position);
+
+            let new_position = match (prev, next) {
+                (None, None) => Some(POSITION_STEP),
+                (Some(prev), None) => prev.checked_add(POSITION_STEP),
+                (None, Some(next)) => next.checked_sub(POSITION_STEP),
+                (Some(prev), Some(next)) => {
+                    let middle = (i64::from(prev) + i64::from(next)) / 2;
+                    if middle > i64::from(prev) && middle < i64::from(next) {
+                        Some(middle as i32)
+                    } else {
+                        None
+                    }
+                }
+            };
+
+            let new_position = match new_position {
+                Some(new_position) => new_position,
+                // No gap left between neighbours. Spread the whole playlist again.
+                None => {
+                    list.insert(index, video);
+                    renumber(&mut list, conn)?;
+                    return Ok(list);
+                }
+            };
+
+            diesel::update(videos.filter(id.eq(video.id.clone())))
+                .set(position.eq(new_position))
+                .execute(conn)
+                .map_err(|err| {
+                    error!("Couldn't move video {:?}: {}", video, err);
+                    err
+                })?;
+
+            Video::list_by_room_id(self.room_id.clone(), conn)
+        })
+    }
+
+    /// Shuffle room's playlist. Returns shuffled playlist.
+    ///
+    /// Video with `first_id`, e.g. the one playing now, is put at the start.
+    pub fn shuffle_by_room_id(
+        room_id_query: String,
+        first_id: Option<String>,
+        conn: &PgConnection,
+    ) -> Result<Vec<Video>, DieselError> {
+        use rand::seq::SliceRandom;
+
+        conn.transaction(|| {
+            lock_playlist(&room_id_query, conn)?;
+
+            let mut list = Video::list_by_room_id(room_id_query.clone(), conn)?;
+            let first = first_id
+                .and_then(|first_id| list.iter().position(|v| v.id == first_id))
+                .map(|index| list.remove(index));
+
+            list.shuffle(&mut rand::thread_rng());
+            if let Some(first) = first {
+                list.insert(0, first);
+            }
+
+            renumber(&mut list, conn)?;
+            Ok(list)
+        })
+    }
+
     pub fn delete(&self, conn: &PgConnection) -> Result<usize, DieselError> {
         use crate::schema::videos::dsl::*;
 
@@ -107,7 +321,6 @@ impl Video {
 #[serde(rename_all = "camelCase")]
 pub struct NewVideo {
     pub room_id: String,
-    pub subtitles_id: Option<String>,
     pub file_id: Option<String>,
     pub url: Option<String>,
     pub title: Option<String>,
@@ -115,18 +328,37 @@ pub struct NewVideo {
     pub is_raw: bool,
     pub is_iframe: bool,
     pub is_live: bool,
+    pub position: i32,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub codec: Option<String>,
+    pub bitrates: Option<Vec<i32>>,
+    pub user_id: Option<String>,
 }
 
 impl NewVideo {
     /// Create multiple NewVideos
+    ///
+    /// Videos are appended to the end of room's playlist, `position` is ignored.
     pub fn bulk_create(
         new_videos: Vec<NewVideo>,
         conn: &PgConnection,
     ) -> Result<Vec<Video>, DieselError> {
+        use crate::schema::videos::dsl::*;
+
         // Use transction for performance reasons.
         conn.transaction(|| {
             let mut result: Vec<Video> = Vec::new();
-            for new_video in new_videos {
+            for mut new_video in new_videos {
+                lock_playlist(&new_video.room_id, conn)?;
+
+                let last_position = videos
+                    .filter(room_id.eq(new_video.room_id.clone()))
+                    .select(diesel::dsl::max(position))
+                    .first::<Option<i32>>(conn)?;
+
+                new_video.position = last_position.unwrap_or(0).saturating_add(POSITION_STEP);
+
                 let created_video = new_video.create(conn)?;
                 result.push(created_video);
             }
@@ -148,13 +380,35 @@ impl NewVideo {
     }
 }
 
+/// Subtitle track of a video.
 #[derive(AsChangeset, Associations, Queryable, Debug, Identifiable, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 #[table_name = "subtitles"]
 pub struct Subtitles {
     pub id: String,
+
+    #[serde(skip_serializing)]
     pub file_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
+
+    pub video_id: String,
+
+    /// BCP 47 language tag, e.g. `en-US`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Track shown to viewers, who didn't pick one.
+    pub is_default: bool,
+
+    /// Milliseconds added to cue timings. Negative value makes subtitles appear earlier.
+    pub time_offset: i32,
+
+    pub created_at: NaiveDateTime,
 }
 
 impl Subtitles {
@@ -170,6 +424,57 @@ impl Subtitles {
             })
             .map_err(From::from)
     }
+
+    /// Tracks of the video, in the order of upload.
+    pub fn list_by_video_id(
+        video_id_query: String,
+        conn: &PgConnection,
+    ) -> Result<Vec<Subtitles>, DieselError> {
+        use crate::schema::subtitles::dsl::*;
+
+        subtitles
+            .filter(video_id.eq(video_id_query.clone()))
+            .order(created_at.asc())
+            .load::<Subtitles>(conn)
+            .map_err(|err| {
+                error!(
+                    "Couldn't query subtitles by video id {:?}: {}",
+                    video_id_query, err
+                );
+                err
+            })
+            .map_err(From::from)
+    }
+
+    /// Save language, label, default flag and timing offset of the track.
+    ///
+    /// Other tracks of the video stop being default, if this one is.
+    pub fn update(&self, conn: &PgConnection) -> Result<Subtitles, DieselError> {
+        use crate::schema::subtitles::dsl::*;
+
+        conn.transaction(|| {
+            if self.is_default {
+                diesel::update(subtitles.filter(video_id.eq(self.video_id.clone())))
+                    .set(is_default.eq(false))
+                    .execute(conn)?;
+            }
+
+            diesel::update(subtitles.filter(id.eq(self.id.clone())))
+                .set((
+                    language.eq(self.language.clone()),
+                    label.eq(self.label.clone()),
+                    is_default.eq(self.is_default),
+                    time_offset.eq(self.time_offset),
+                ))
+                .get_result::<Subtitles>(conn)
+        })
+        .map_err(|err| {
+            error!("Couldn't update subtitles {:?}: {}", self, err);
+            err
+        })
+        .map_err(From::from)
+    }
+
     pub fn delete(&self, conn: &PgConnection) -> Result<usize, DieselError> {
         use crate::schema::subtitles::dsl::*;
         let query_id = self.id.to_owned();
@@ -190,19 +495,33 @@ impl Subtitles {
 pub struct NewSubtitles {
     pub file_id: String,
     pub url: Option<String>,
+    pub video_id: String,
+    pub language: Option<String>,
+    pub label: Option<String>,
+    pub is_default: bool,
+    pub time_offset: i32,
 }
 
 impl NewSubtitles {
+    /// Other tracks of the video stop being default, if this one is.
     pub fn create(&self, conn: &PgConnection) -> Result<Subtitles, DieselError> {
         use crate::schema::subtitles::dsl::*;
 
-        diesel::insert_into(subtitles)
-            .values(self)
-            .get_result::<Subtitles>(conn)
-            .map_err(|err| {
-                error!("Couldn't create subtitles {:?}: {}", self, err);
-                err
-            })
-            .map_err(From::from)
+        conn.transaction(|| {
+            if self.is_default {
+                diesel::update(subtitles.filter(video_id.eq(self.video_id.clone())))
+                    .set(is_default.eq(false))
+                    .execute(conn)?;
+            }
+
+            diesel::insert_into(subtitles)
+                .values(self)
+                .get_result::<Subtitles>(conn)
+        })
+        .map_err(|err| {
+            error!("Couldn't create subtitles {:?}: {}", self, err);
+            err
+        })
+        .map_err(From::from)
     }
 }
@@ -11,4 +11,16 @@ lazy_static! {
     pub static ref DISCORD_CLIENT_ID: String = var("DISCORD_CLIENT_ID").unwrap_or_default();
     pub static ref DISCORD_CLIENT_SECRET: String = var("DISCORD_CLIENT_SECRET").unwrap_or_default();
     pub static ref DISCORD_REDIRECT_URL: String = var("DISCORD_REDIRECT_URL").unwrap_or_default();
+    pub static ref UPLOADS_DIR: String =
+        var("UPLOADS_DIR").unwrap_or_else(|_| String::from("uploads"));
+    pub static ref FILES_GC_DRY_RUN: bool =
+        var("FILES_GC_DRY_RUN").map(|v| v == "1" || v == "true").unwrap_or(false);
+    pub static ref STORAGE_BACKEND: String =
+        var("STORAGE_BACKEND").unwrap_or_else(|_| String::from("local"));
+    pub static ref S3_ENDPOINT: String = var("S3_ENDPOINT").unwrap_or_default();
+    pub static ref S3_REGION: String =
+        var("S3_REGION").unwrap_or_else(|_| String::from("us-east-1"));
+    pub static ref S3_BUCKET: String = var("S3_BUCKET").unwrap_or_default();
+    pub static ref S3_ACCESS_KEY: String = var("S3_ACCESS_KEY").unwrap_or_default();
+    pub static ref S3_SECRET_KEY: String = var("S3_SECRET_KEY").unwrap_or_default();
 }
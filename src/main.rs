@@ -13,8 +13,12 @@ extern crate log;
 pub mod db;
 mod debug;
 pub mod env;
+pub mod media;
 pub mod schema;
 pub mod server;
+pub mod storage;
+#[cfg(test)]
+mod testing;
 pub mod vars;
 
 fn main() {
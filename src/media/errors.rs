@@ -0,0 +1,23 @@
+use failure::Fail;
+
+#[derive(Fail, Debug)]
+pub enum MediaError {
+    #[fail(display = "Invalid url")]
+    InvalidUrl,
+    #[fail(display = "Unsupported url")]
+    UnsupportedUrl,
+    #[fail(display = "Video is unavailable")]
+    Unavailable,
+    #[fail(display = "Request failed: {}", _0)]
+    Request(String),
+    #[fail(display = "Invalid media: {}", _0)]
+    InvalidMedia(&'static str),
+    #[fail(display = "Invalid subtitles: {}", _0)]
+    InvalidSubtitles(&'static str),
+}
+
+impl From<reqwest::Error> for MediaError {
+    fn from(err: reqwest::Error) -> MediaError {
+        MediaError::Request(err.to_string())
+    }
+}
@@ -0,0 +1,194 @@
+// HLS playlists inspection.
+//
+// Only tags, needed to tell live streams from VOD and to get stream variants, are parsed.
+
+use super::{is_public_url, MediaError};
+use reqwest::{StatusCode, Url};
+
+/// Playlists bigger than that are not read.
+const MAX_PLAYLIST_SIZE: usize = 2 * 1024 * 1024;
+
+/// Variant stream of master playlist.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub uri: Url,
+    /// Peak bitrate in bits per second.
+    pub bandwidth: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Playlist {
+    Master {
+        variants: Vec<Variant>,
+    },
+    Media {
+        /// Sum of segment durations in seconds.
+        duration: f64,
+        /// Playlist has `#EXT-X-ENDLIST` tag or is of VOD type, so no segments will be added.
+        is_ended: bool,
+    },
+}
+
+/// What is known about HLS stream.
+#[derive(Debug, Clone, Default)]
+pub struct StreamInfo {
+    pub is_live: bool,
+    /// Duration in seconds, for ended streams only.
+    pub duration: Option<f64>,
+    /// Bitrates of variant streams in bits per second, in ascending order.
+    pub bitrates: Vec<u64>,
+    /// Dimensions of the best variant.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Split attribute list, e.g. `BANDWIDTH=1280000,CODECS="mp4a.40.2,avc1.4d401e"`.
+fn attributes(list: &str) -> Vec<(&str, &str)> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+
+    for (i, c) in list
+        .char_indices()
+        .chain(std::iter::once((list.len(), ',')))
+    {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                let attribute = &list[start..i];
+                if let Some(eq) = attribute.find('=') {
+                    let value = attribute[eq + 1..].trim().trim_matches('"');
+                    result.push((attribute[..eq].trim(), value));
+                }
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    result
+}
+
+fn parse_variant(attributes_list: &str, uri: Url) -> Variant {
+    let mut variant = Variant {
+        uri,
+        bandwidth: None,
+        width: None,
+        height: None,
+    };
+
+    for (name, value) in attributes(attributes_list) {
+        match name {
+            "BANDWIDTH" => variant.bandwidth = value.parse().ok(),
+            "RESOLUTION" => {
+                let mut dimensions = value.splitn(2, 'x');
+                variant.width = dimensions.next().and_then(|w| w.parse().ok());
+                variant.height = dimensions.next().and_then(|h| h.parse().ok());
+            }
+            _ => (),
+        }
+    }
+
+    variant
+}
+
+/// Parse playlist, resolving uris relative to `url`.
+pub fn parse(url: &Url, text: &str) -> Result<Playlist, MediaError> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+    if lines.next() != Some("#EXTM3U") {
+        return Err(MediaError::InvalidMedia("Not an m3u8 playlist"));
+    }
+
+    let mut variants = Vec::new();
+    let mut stream_inf = None;
+    let mut duration = 0.0;
+    let mut is_ended = false;
+
+    for line in lines {
+        if let Some(attributes_list) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            stream_inf = Some(attributes_list);
+        } else if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let segment_duration = extinf.split(',').next().unwrap_or_default();
+            duration += segment_duration.trim().parse::<f64>().unwrap_or(0.0);
+        } else if line == "#EXT-X-ENDLIST" || line == "#EXT-X-PLAYLIST-TYPE:VOD" {
+            is_ended = true;
+        } else if !line.starts_with('#') {
+            // Uri line. Only variant uris are interesting, segments are counted by `#EXTINF`.
+            if let Some(attributes_list) = stream_inf.take() {
+                let uri = url
+                    .join(line)
+                    .map_err(|_| MediaError::InvalidMedia("Invalid variant uri"))?;
+                variants.push(parse_variant(attributes_list, uri));
+            }
+        }
+    }
+
+    if !variants.is_empty() {
+        return Ok(Playlist::Master { variants });
+    }
+
+    Ok(Playlist::Media { duration, is_ended })
+}
+
+async fn fetch(client: &reqwest::Client, url: &Url) -> Result<Playlist, MediaError> {
+    let mut res = client.get(url.clone()).send().await?;
+
+    match res.status() {
+        status if status.is_success() => (),
+        StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::FORBIDDEN => {
+            return Err(MediaError::Unavailable)
+        }
+        status => return Err(MediaError::Request(status.to_string())),
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() > MAX_PLAYLIST_SIZE {
+            return Err(MediaError::InvalidMedia("Playlist is too big"));
+        }
+    }
+
+    parse(url, &String::from_utf8_lossy(&data))
+}
+
+/// Fetch playlist and, if it's a master playlist, its first variant.
+pub async fn inspect(client: &reqwest::Client, url: &Url) -> Result<StreamInfo, MediaError> {
+    let mut info = StreamInfo::default();
+
+    let media = match fetch(client, url).await? {
+        Playlist::Master { variants } => {
+            let best = variants.iter().max_by_key(|v| v.bandwidth.unwrap_or(0));
+            info.width = best.and_then(|v| v.width);
+            info.height = best.and_then(|v| v.height);
+
+            info.bitrates = variants.iter().filter_map(|v| v.bandwidth).collect();
+            info.bitrates.sort();
+            info.bitrates.dedup();
+
+            // Variants are renditions of the same stream, so one is enough to tell if it's live.
+            let variant = &variants[0];
+            if !is_public_url(&variant.uri) {
+                return Err(MediaError::InvalidMedia("Variant is not on a public host"));
+            }
+            match fetch(client, &variant.uri).await? {
+                Playlist::Media { duration, is_ended } => (duration, is_ended),
+                Playlist::Master { .. } => {
+                    return Err(MediaError::InvalidMedia("Nested master playlist"))
+                }
+            }
+        }
+        Playlist::Media { duration, is_ended } => (duration, is_ended),
+    };
+
+    let (duration, is_ended) = media;
+    info.is_live = !is_ended;
+    if is_ended && duration > 0.0 {
+        info.duration = Some(duration);
+    }
+
+    Ok(info)
+}
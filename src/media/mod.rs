@@ -0,0 +1,12 @@
+mod errors;
+mod hls;
+mod probe;
+mod providers;
+mod resolver;
+mod subtitles;
+
+pub use errors::*;
+pub use probe::{probe, ProbeInfo};
+pub use providers::*;
+pub use resolver::*;
+pub use subtitles::{to_vtt, SubtitlesFormat};
@@ -0,0 +1,343 @@
+use super::{bytes, ProbeInfo};
+use crate::media::MediaError;
+use std::convert::TryInto;
+
+// Element ids
+const EBML: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43_B675;
+
+const TRACK_TYPE_VIDEO: u64 = 1;
+const TRACK_TYPE_AUDIO: u64 = 2;
+
+/// Default timecode scale, 1ms in nanoseconds.
+const DEFAULT_TIMECODE_SCALE: u64 = 1_000_000;
+
+struct Element {
+    id: u32,
+    /// `None` if element size is unknown, which is allowed for segments and clusters.
+    size: Option<u64>,
+    /// Position right after element's header.
+    data_start: usize,
+}
+
+impl Element {
+    /// Data of the element, if it's of known size and fits in `buf`.
+    fn data<'a>(&self, buf: &'a [u8]) -> Option<&'a [u8]> {
+        bytes(buf, self.data_start, self.size?.try_into().ok()?)
+    }
+
+    /// Data of the element, truncated to `buf`.
+    fn partial_data<'a>(&self, buf: &'a [u8]) -> &'a [u8] {
+        let end = match self.size {
+            Some(size) => (self.data_start as u64).saturating_add(size),
+            None => buf.len() as u64,
+        };
+        let end = end.min(buf.len() as u64) as usize;
+
+        buf.get(self.data_start..end).unwrap_or_default()
+    }
+}
+
+/// Element id keeps its length marker, e.g. `0x1A45DFA3`.
+fn read_id(buf: &[u8], pos: usize) -> Option<(u32, usize)> {
+    let first = *buf.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 4 {
+        return None;
+    }
+
+    let id = bytes(buf, pos, len)?
+        .iter()
+        .fold(0, |id, b| (id << 8) | u32::from(*b));
+    Some((id, len))
+}
+
+/// Returns `None` as size if it's unknown (all value bits are set).
+fn read_size(buf: &[u8], pos: usize) -> Option<(Option<u64>, usize)> {
+    let first = *buf.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+
+    let value = bytes(buf, pos + 1, len - 1)?
+        .iter()
+        .fold(u64::from(first) & (0xFF >> len), |value, b| {
+            (value << 8) | u64::from(*b)
+        });
+
+    let unknown = (1 << (7 * len)) - 1;
+    if value == unknown {
+        Some((None, len))
+    } else {
+        Some((Some(value), len))
+    }
+}
+
+fn read_element(buf: &[u8], pos: usize) -> Option<Element> {
+    let (id, id_len) = read_id(buf, pos)?;
+    let (size, size_len) = read_size(buf, pos + id_len)?;
+
+    Some(Element {
+        id,
+        size,
+        data_start: pos + id_len + size_len,
+    })
+}
+
+/// Iterate over elements of known size, contained in `buf`.
+fn children(buf: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        let element = read_element(buf, pos)?;
+        let data = element.data(buf)?;
+        pos = element.data_start + data.len();
+        Some((element.id, data))
+    })
+}
+
+fn read_uint(data: &[u8]) -> Option<u64> {
+    if data.len() > 8 {
+        return None;
+    }
+
+    Some(data.iter().fold(0, |value, b| (value << 8) | u64::from(*b)))
+}
+
+fn read_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f64::from(f32::from_be_bytes(data.try_into().ok()?))),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+pub fn is_matroska(head: &[u8]) -> bool {
+    read_id(head, 0).map(|(id, _)| id) == Some(EBML)
+}
+
+/// Parse segment info and tracks, which precede media data.
+pub fn parse(head: &[u8]) -> Result<ProbeInfo, MediaError> {
+    let header = read_element(head, 0).ok_or(MediaError::InvalidMedia("Malformed EBML header"))?;
+    let header_data = header
+        .data(head)
+        .ok_or(MediaError::InvalidMedia("Malformed EBML header"))?;
+
+    let pos = header.data_start + header_data.len();
+    let segment = read_element(head, pos)
+        .filter(|segment| segment.id == SEGMENT)
+        .ok_or(MediaError::InvalidMedia("No segment"))?;
+
+    // Segment is usually way bigger than head, so its children are read until head ends.
+    let segment_data = segment.partial_data(head);
+
+    let mut info = ProbeInfo::default();
+    let mut pos = 0;
+    while let Some(element) = read_element(segment_data, pos) {
+        if element.id == CLUSTER {
+            break;
+        }
+
+        let data = match element.data(segment_data) {
+            Some(data) => data,
+            None => break,
+        };
+
+        match element.id {
+            INFO => info.duration = parse_info(data),
+            TRACKS => parse_tracks(data, &mut info),
+            _ => (),
+        }
+
+        pos = element.data_start + data.len();
+    }
+
+    Ok(info)
+}
+
+/// Duration in seconds.
+fn parse_info(data: &[u8]) -> Option<f64> {
+    let mut timecode_scale = DEFAULT_TIMECODE_SCALE;
+    let mut duration = None;
+
+    for (id, data) in children(data) {
+        match id {
+            TIMECODE_SCALE => timecode_scale = read_uint(data).unwrap_or(timecode_scale),
+            DURATION => duration = read_float(data),
+            _ => (),
+        }
+    }
+
+    duration
+        .map(|duration| duration * timecode_scale as f64 / 1_000_000_000.0)
+        .filter(|duration| *duration > 0.0)
+}
+
+fn parse_tracks(data: &[u8], info: &mut ProbeInfo) {
+    let mut audio_codec = None;
+
+    for (_, entry) in children(data).filter(|(id, _)| *id == TRACK_ENTRY) {
+        let mut track_type = None;
+        let mut codec = None;
+        let mut video = None;
+
+        for (id, data) in children(entry) {
+            match id {
+                TRACK_TYPE => track_type = read_uint(data),
+                CODEC_ID => {
+                    let codec_id = String::from_utf8_lossy(data);
+                    codec = Some(codec_id.trim_end_matches('\0').to_owned());
+                }
+                VIDEO => video = Some(data),
+                _ => (),
+            }
+        }
+
+        match track_type {
+            Some(TRACK_TYPE_VIDEO) if info.codec.is_none() => {
+                info.codec = codec;
+
+                for (id, data) in children(video.unwrap_or_default()) {
+                    match id {
+                        PIXEL_WIDTH => info.width = read_uint(data).map(|w| w as u32),
+                        PIXEL_HEIGHT => info.height = read_uint(data).map(|h| h as u32),
+                        _ => (),
+                    }
+                }
+            }
+            Some(TRACK_TYPE_AUDIO) if audio_codec.is_none() => audio_codec = codec,
+            _ => (),
+        }
+    }
+
+    if info.codec.is_none() {
+        info.codec = audio_codec;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Element with its id in big-endian bytes and 8-byte size.
+    fn element(id: u32, data: &[u8]) -> Vec<u8> {
+        let id = id.to_be_bytes();
+        let id_start = id.iter().position(|b| *b != 0).unwrap();
+
+        let mut element = id[id_start..].to_vec();
+        element.push(0x01);
+        element.extend_from_slice(&(data.len() as u64).to_be_bytes()[1..]);
+        element.extend_from_slice(data);
+        element
+    }
+
+    fn track(track_type: u8, codec: &str, video: Option<(u16, u16)>) -> Vec<u8> {
+        let mut entry = [
+            element(TRACK_TYPE, &[track_type]),
+            element(CODEC_ID, codec.as_bytes()),
+        ]
+        .concat();
+        if let Some((width, height)) = video {
+            let video = [
+                element(PIXEL_WIDTH, &width.to_be_bytes()),
+                element(PIXEL_HEIGHT, &height.to_be_bytes()),
+            ]
+            .concat();
+            entry.extend(element(VIDEO, &video));
+        }
+        element(TRACK_ENTRY, &entry)
+    }
+
+    /// WebM file with segment of unknown size, as written by live encoders.
+    fn webm(info: &[u8], tracks: &[Vec<u8>]) -> Vec<u8> {
+        let header = element(EBML, &element(0x4282, b"webm"));
+        let segment = [
+            element(INFO, info),
+            element(TRACKS, &tracks.concat()),
+            element(CLUSTER, &[0; 64]),
+        ]
+        .concat();
+
+        [
+            header,
+            vec![
+                0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+            segment,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn parses_video() {
+        let info = [
+            element(TIMECODE_SCALE, &[0x0F, 0x42, 0x40]),
+            element(DURATION, &12_345.0f64.to_be_bytes()),
+        ]
+        .concat();
+        let tracks = [
+            track(2, "A_OPUS", None),
+            track(1, "V_VP9", Some((854, 480))),
+        ];
+        let file = webm(&info, &tracks);
+        assert!(is_matroska(&file));
+
+        let info = parse(&file).unwrap();
+        assert_eq!(info.duration, Some(12.345));
+        assert_eq!(info.width, Some(854));
+        assert_eq!(info.height, Some(480));
+        assert_eq!(info.codec.as_deref(), Some("V_VP9"));
+    }
+
+    #[test]
+    fn parses_audio_with_custom_timecode_scale() {
+        let info = [
+            element(TIMECODE_SCALE, &[0x3B, 0x9A, 0xCA, 0x00]),
+            element(DURATION, &90.5f32.to_be_bytes()),
+        ]
+        .concat();
+        let file = webm(&info, &[track(2, "A_VORBIS", None)]);
+
+        let info = parse(&file).unwrap();
+        assert_eq!(info.duration, Some(90.5));
+        assert_eq!(info.width, None);
+        assert_eq!(info.codec.as_deref(), Some("A_VORBIS"));
+    }
+
+    #[test]
+    fn truncated_head_gives_what_it_has() {
+        let info = element(DURATION, &5000.0f64.to_be_bytes());
+        let file = webm(&info, &[track(1, "V_VP8", Some((640, 360)))]);
+        let tracks_start = file
+            .windows(4)
+            .position(|window| window == TRACKS.to_be_bytes())
+            .unwrap();
+
+        let info = parse(&file[..tracks_start + 20]).unwrap();
+        assert_eq!(info.duration, Some(5.0));
+        assert_eq!(info.codec, None);
+    }
+
+    #[test]
+    fn malformed_files_are_invalid() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&element(EBML, &[])).is_err());
+        assert!(parse(&[0x1A, 0x45, 0xDF, 0xA3, 0x00]).is_err());
+
+        let header = element(EBML, &[]);
+        let not_segment = [header, element(INFO, &[])].concat();
+        assert!(parse(&not_segment).is_err());
+    }
+}
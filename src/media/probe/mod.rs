@@ -0,0 +1,237 @@
+// Native probing of media files.
+//
+// Only the parts of file, which contain container metadata, are read using range requests.
+// Supported containers are MP4 (ISO BMFF), Matroska/WebM and Ogg.
+
+use super::MediaError;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{StatusCode, Url};
+use std::convert::TryInto;
+
+mod matroska;
+mod mp4;
+mod ogg;
+
+/// How many bytes to read from the start of file to detect container.
+const HEAD_SIZE: u64 = 512 * 1024;
+/// Minimal size of a range request.
+const MIN_READ_SIZE: u64 = 64 * 1024;
+/// Max number of range requests per file.
+const MAX_REQUESTS: usize = 6;
+
+/// Metadata extracted from media container.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeInfo {
+    /// Duration in seconds.
+    pub duration: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Codec of the video track, or of the audio track if there is no video.
+    pub codec: Option<String>,
+}
+
+/// Probe media file behind the url.
+pub async fn probe(client: &reqwest::Client, url: &Url) -> Result<ProbeInfo, MediaError> {
+    let mut reader = RangeReader::new(client, url);
+    let head = reader.read(0, HEAD_SIZE).await?;
+
+    if mp4::is_mp4(&head) {
+        mp4::probe(&mut reader).await
+    } else if matroska::is_matroska(&head) {
+        matroska::parse(&head)
+    } else if ogg::is_ogg(&head) {
+        ogg::probe(&mut reader, &head).await
+    } else {
+        Err(MediaError::InvalidMedia("Unknown container"))
+    }
+}
+
+/// Reads parts of remote file with http range requests.
+///
+/// The last read part is cached, so small sequential reads don't produce extra requests.
+pub struct RangeReader<'a> {
+    client: &'a reqwest::Client,
+    url: &'a Url,
+    /// Total size of the file, known after the first request.
+    size: Option<u64>,
+    /// Offset and content of the last read part.
+    cache: Option<(u64, Vec<u8>)>,
+    requests: usize,
+}
+
+impl<'a> RangeReader<'a> {
+    fn new(client: &'a reqwest::Client, url: &'a Url) -> RangeReader<'a> {
+        RangeReader {
+            client,
+            url,
+            size: None,
+            cache: None,
+            requests: 0,
+        }
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Read `len` bytes starting at `offset`.
+    ///
+    /// Result is shorter than `len` if file ends earlier.
+    pub async fn read(&mut self, offset: u64, len: u64) -> Result<Vec<u8>, MediaError> {
+        // Offsets come from the file itself, so they may be anything.
+        let read_end = offset
+            .checked_add(len)
+            .ok_or(MediaError::InvalidMedia("Read past the end of file"))?;
+
+        if let Some((start, data)) = &self.cache {
+            let end = start + data.len() as u64;
+            let is_eof = self.size == Some(end);
+            if offset >= *start && (read_end <= end || is_eof) {
+                let from = (offset - start).min(data.len() as u64) as usize;
+                let to = (read_end - start).min(data.len() as u64) as usize;
+                return Ok(data[from..to].to_vec());
+            }
+        }
+
+        let data = self.fetch(offset, len.max(MIN_READ_SIZE)).await?;
+        let result = data[..(len as usize).min(data.len())].to_vec();
+        self.cache = Some((offset, data));
+        Ok(result)
+    }
+
+    async fn fetch(&mut self, offset: u64, len: u64) -> Result<Vec<u8>, MediaError> {
+        if self.requests >= MAX_REQUESTS {
+            return Err(MediaError::InvalidMedia("Too many reads"));
+        }
+        self.requests += 1;
+
+        if let Some(size) = self.size {
+            if offset >= size {
+                return Ok(Vec::new());
+            }
+        }
+
+        let last = len
+            .checked_sub(1)
+            .and_then(|len| offset.checked_add(len))
+            .ok_or(MediaError::InvalidMedia("Read past the end of file"))?;
+
+        let mut res = self
+            .client
+            .get(self.url.clone())
+            .header(RANGE, format!("bytes={}-{}", offset, last))
+            .send()
+            .await?;
+
+        match res.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                self.size = res
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.rsplit('/').next())
+                    .and_then(|v| v.parse().ok());
+            }
+            // Server ignored range, whole file is being sent.
+            StatusCode::OK => {
+                self.size = res.content_length();
+                if offset > 0 {
+                    return Err(MediaError::Request(String::from(
+                        "Range requests are not supported",
+                    )));
+                }
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => return Ok(Vec::new()),
+            StatusCode::NOT_FOUND | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(MediaError::Unavailable)
+            }
+            status => return Err(MediaError::Request(status.to_string())),
+        }
+
+        let mut data = Vec::new();
+        while (data.len() as u64) < len {
+            match res.chunk().await? {
+                Some(chunk) => data.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        data.truncate(len as usize);
+
+        Ok(data)
+    }
+}
+
+fn bytes<'a>(buf: &'a [u8], pos: usize, len: usize) -> Option<&'a [u8]> {
+    buf.get(pos..pos.checked_add(len)?)
+}
+
+fn be_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes(buf, pos, 2)?.try_into().ok()?))
+}
+
+fn be_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes(buf, pos, 4)?.try_into().ok()?))
+}
+
+fn be_u64(buf: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes(buf, pos, 8)?.try_into().ok()?))
+}
+
+fn le_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes(buf, pos, 2)?.try_into().ok()?))
+}
+
+fn le_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes(buf, pos, 4)?.try_into().ok()?))
+}
+
+fn le_i64(buf: &[u8], pos: usize) -> Option<i64> {
+    Some(i64::from_le_bytes(bytes(buf, pos, 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Response, StubServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn reads_are_served_from_the_last_part() {
+        let data: Vec<u8> = (0..=255).cycle().take(200_000).collect();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = StubServer::start(move |req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::file(req, "application/octet-stream", &data)
+        })
+        .await;
+
+        let client = testing::client();
+        let url = Url::parse(&server.url("/file")).unwrap();
+        let mut reader = RangeReader::new(&client, &url);
+
+        assert_eq!(reader.read(10, 4).await.unwrap(), vec![10, 11, 12, 13]);
+        assert_eq!(reader.read(300, 2).await.unwrap(), vec![44, 45]);
+        assert_eq!(reader.size(), Some(200_000));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        assert_eq!(reader.read(199_998, 10).await.unwrap(), vec![62, 63]);
+        assert_eq!(reader.read(300_000, 10).await.unwrap(), Vec::<u8>::new());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn reads_past_the_end_of_offsets_are_refused() {
+        let client = testing::client();
+        let url = Url::parse("http://127.0.0.1:9/file").unwrap();
+        let mut reader = RangeReader::new(&client, &url);
+
+        let err = reader.read(u64::max_value() - 10, 100).await.unwrap_err();
+        assert!(matches!(err, MediaError::InvalidMedia(_)));
+
+        // Reads are extended to `MIN_READ_SIZE`, which mustn't overflow either.
+        let err = reader.read(u64::max_value() - 10, 10).await.unwrap_err();
+        assert!(matches!(err, MediaError::InvalidMedia(_)));
+    }
+}
@@ -0,0 +1,299 @@
+use super::{be_u16, be_u32, be_u64, bytes, ProbeInfo, RangeReader};
+use crate::media::MediaError;
+
+/// `moov` boxes bigger than that are not read.
+const MAX_MOOV_SIZE: u64 = 16 * 1024 * 1024;
+/// Length of the largest box header, with 64-bit size.
+const MAX_HEADER_LEN: u64 = 16;
+
+struct BoxHeader {
+    kind: [u8; 4],
+    /// 8 or 16 bytes
+    header_len: u64,
+    /// Size of the whole box, including header. `None` if box lasts until the end of file.
+    size: Option<u64>,
+}
+
+fn box_header(buf: &[u8], pos: usize) -> Option<BoxHeader> {
+    let size = be_u32(buf, pos)?;
+    let mut kind = [0; 4];
+    kind.copy_from_slice(bytes(buf, pos + 4, 4)?);
+
+    let (header_len, size) = match size {
+        0 => (8, None),
+        1 => (16, Some(be_u64(buf, pos + 8)?)),
+        size => (8, Some(u64::from(size))),
+    };
+
+    Some(BoxHeader {
+        kind,
+        header_len,
+        size,
+    })
+}
+
+/// Iterate over boxes, contained in `buf`.
+fn children(buf: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        let header = box_header(buf, pos)?;
+        let start = pos + header.header_len as usize;
+        let end = match header.size {
+            Some(size) if size >= header.header_len => pos.checked_add(size as usize)?,
+            Some(_) => return None,
+            None => buf.len(),
+        };
+
+        let body = buf.get(start..end)?;
+        pos = end;
+        Some((header.kind, body))
+    })
+}
+
+fn child<'a>(buf: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    children(buf).find(|(k, _)| k == kind).map(|(_, body)| body)
+}
+
+pub fn is_mp4(head: &[u8]) -> bool {
+    bytes(head, 4, 4) == Some(b"ftyp")
+}
+
+/// Find `moov` box among top level boxes and parse it.
+///
+/// `moov` is often placed after media data, so boxes are walked by their headers only.
+pub async fn probe(reader: &mut RangeReader<'_>) -> Result<ProbeInfo, MediaError> {
+    let mut offset = 0;
+
+    loop {
+        let buf = reader.read(offset, MAX_HEADER_LEN).await?;
+        let header =
+            box_header(&buf, 0).ok_or(MediaError::InvalidMedia("Couldn't find moov box"))?;
+
+        let size = match header.size {
+            Some(size) => size,
+            None => reader
+                .size()
+                .map(|file_size| file_size.saturating_sub(offset))
+                .ok_or(MediaError::InvalidMedia("Couldn't find moov box"))?,
+        };
+
+        if size < header.header_len {
+            return Err(MediaError::InvalidMedia("Malformed box"));
+        }
+
+        if &header.kind == b"moov" {
+            if size > MAX_MOOV_SIZE {
+                return Err(MediaError::InvalidMedia("moov box is too big"));
+            }
+
+            let moov = reader.read(offset, size).await?;
+            let body = moov
+                .get(header.header_len as usize..)
+                .ok_or(MediaError::InvalidMedia("Malformed moov box"))?;
+            return parse_moov(body);
+        }
+
+        offset = offset
+            .checked_add(size)
+            .ok_or(MediaError::InvalidMedia("Malformed box"))?;
+    }
+}
+
+#[derive(Default)]
+struct Track {
+    handler: Option<[u8; 4]>,
+    codec: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+fn parse_moov(moov: &[u8]) -> Result<ProbeInfo, MediaError> {
+    let mvhd = child(moov, b"mvhd").ok_or(MediaError::InvalidMedia("No mvhd box"))?;
+
+    // Fragmented files keep their duration in `mehd` box.
+    let mut duration = parse_mvhd(mvhd).filter(|d| *d > 0.0);
+    if duration.is_none() {
+        let timescale = be_u32(mvhd, if mvhd.first() == Some(&1) { 20 } else { 12 });
+        let mehd = child(moov, b"mvex").and_then(|mvex| child(mvex, b"mehd"));
+        if let (Some(timescale), Some(mehd)) = (timescale, mehd) {
+            duration = parse_mehd(mehd, timescale);
+        }
+    }
+
+    let tracks: Vec<Track> = children(moov)
+        .filter(|(kind, _)| kind == b"trak")
+        .map(|(_, trak)| parse_trak(trak))
+        .collect();
+
+    let video = tracks.iter().find(|t| t.handler == Some(*b"vide"));
+    let audio = tracks.iter().find(|t| t.handler == Some(*b"soun"));
+
+    Ok(ProbeInfo {
+        duration,
+        width: video.and_then(|t| t.width),
+        height: video.and_then(|t| t.height),
+        codec: video.or(audio).and_then(|t| t.codec.clone()),
+    })
+}
+
+/// Duration in seconds.
+fn parse_mvhd(mvhd: &[u8]) -> Option<f64> {
+    let (timescale, duration) = match mvhd.first()? {
+        1 => {
+            let duration = be_u64(mvhd, 24)?;
+            if duration == u64::max_value() {
+                return None;
+            }
+            (be_u32(mvhd, 20)?, duration)
+        }
+        _ => {
+            let duration = be_u32(mvhd, 16)?;
+            if duration == u32::max_value() {
+                return None;
+            }
+            (be_u32(mvhd, 12)?, u64::from(duration))
+        }
+    };
+
+    if timescale == 0 {
+        return None;
+    }
+
+    Some(duration as f64 / f64::from(timescale))
+}
+
+/// Duration of fragmented file in seconds.
+fn parse_mehd(mehd: &[u8], timescale: u32) -> Option<f64> {
+    let duration = match mehd.first()? {
+        1 => be_u64(mehd, 4)?,
+        _ => u64::from(be_u32(mehd, 4)?),
+    };
+
+    if timescale == 0 || duration == 0 {
+        return None;
+    }
+
+    Some(duration as f64 / f64::from(timescale))
+}
+
+fn parse_trak(trak: &[u8]) -> Track {
+    let mut track = Track::default();
+
+    if let Some(tkhd) = child(trak, b"tkhd") {
+        // Width and height are 16.16 fixed point numbers at the end of the box.
+        let pos = if tkhd.first() == Some(&1) { 88 } else { 76 };
+        track.width = be_u16(tkhd, pos).map(u32::from).filter(|w| *w > 0);
+        track.height = be_u16(tkhd, pos + 4).map(u32::from).filter(|h| *h > 0);
+    }
+
+    let mdia = match child(trak, b"mdia") {
+        Some(mdia) => mdia,
+        None => return track,
+    };
+
+    if let Some(hdlr) = child(mdia, b"hdlr") {
+        let mut handler = [0; 4];
+        if let Some(kind) = bytes(hdlr, 8, 4) {
+            handler.copy_from_slice(kind);
+            track.handler = Some(handler);
+        }
+    }
+
+    // The first sample entry of `stsd` box is named after codec, e.g. `avc1` or `mp4a`.
+    track.codec = child(mdia, b"minf")
+        .and_then(|minf| child(minf, b"stbl"))
+        .and_then(|stbl| child(stbl, b"stsd"))
+        .and_then(|stsd| bytes(stsd, 12, 4))
+        .map(|codec| String::from_utf8_lossy(codec).trim().to_owned());
+
+    track
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, mp4_box, mp4_file, Response, StubServer};
+
+    async fn probe_data(data: Vec<u8>) -> Result<ProbeInfo, MediaError> {
+        let server = StubServer::start(move |req| Response::file(req, "video/mp4", &data)).await;
+        let url = reqwest::Url::parse(&server.url("/video.mp4")).unwrap();
+        super::super::probe(&testing::client(), &url).await
+    }
+
+    #[test]
+    fn parses_moov() {
+        let file = mp4_file(90_500, 1280, 720, 16);
+        let moov = child(&file, b"moov").unwrap();
+
+        let info = parse_moov(moov).unwrap();
+        assert_eq!(info.duration, Some(90.5));
+        assert_eq!(info.width, Some(1280));
+        assert_eq!(info.height, Some(720));
+        assert_eq!(info.codec.as_deref(), Some("avc1"));
+    }
+
+    #[test]
+    fn parses_version_1_mvhd() {
+        let mut mvhd = vec![0; 112];
+        mvhd[0] = 1;
+        mvhd[20..24].copy_from_slice(&90_000u32.to_be_bytes());
+        mvhd[24..32].copy_from_slice(&(90_000u64 * 3600).to_be_bytes());
+
+        let info = parse_moov(&mp4_box(b"mvhd", &mvhd)).unwrap();
+        assert_eq!(info.duration, Some(3600.0));
+        assert_eq!(info.codec, None);
+    }
+
+    #[test]
+    fn fragmented_file_has_duration_in_mehd() {
+        let mut mvhd = vec![0; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        let mehd = [0, 0, 0, 0, 0, 0, 0x2E, 0xE0];
+        let moov = [
+            mp4_box(b"mvhd", &mvhd),
+            mp4_box(b"mvex", &mp4_box(b"mehd", &mehd)),
+        ]
+        .concat();
+
+        let info = parse_moov(&moov).unwrap();
+        assert_eq!(info.duration, Some(12.0));
+    }
+
+    #[test]
+    fn moov_without_mvhd_is_invalid() {
+        assert!(parse_moov(&mp4_box(b"trak", &[])).is_err());
+        assert!(parse_moov(&[0, 0, 0, 200, b'm', b'v', b'h', b'd']).is_err());
+    }
+
+    #[tokio::test]
+    async fn finds_moov_after_media_data() {
+        let info = probe_data(mp4_file(5000, 640, 360, 300_000)).await.unwrap();
+        assert_eq!(info.duration, Some(5.0));
+        assert_eq!(info.width, Some(640));
+    }
+
+    #[tokio::test]
+    async fn huge_box_size_is_malformed() {
+        // 64-bit size of `mdat` points way past any offset.
+        let mut mdat = 1u32.to_be_bytes().to_vec();
+        mdat.extend_from_slice(b"mdat");
+        mdat.extend_from_slice(&(u64::max_value() - 8).to_be_bytes());
+        let data = [mp4_box(b"ftyp", b"isom\0\0\0\0isom"), mdat].concat();
+
+        let err = probe_data(data).await.unwrap_err();
+        assert!(matches!(err, MediaError::InvalidMedia(_)));
+    }
+
+    #[tokio::test]
+    async fn box_smaller_than_its_header_is_malformed() {
+        let data = [
+            mp4_box(b"ftyp", b"isom\0\0\0\0isom"),
+            vec![0, 0, 0, 4, b'f', b'r', b'e', b'e'],
+        ]
+        .concat();
+
+        let err = probe_data(data).await.unwrap_err();
+        assert!(matches!(err, MediaError::InvalidMedia("Malformed box")));
+    }
+}
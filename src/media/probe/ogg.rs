@@ -0,0 +1,326 @@
+use super::{be_u16, be_u32, bytes, le_i64, le_u16, le_u32, ProbeInfo, RangeReader};
+use crate::media::MediaError;
+use std::collections::HashMap;
+
+const CAPTURE_PATTERN: &[u8] = b"OggS";
+/// Size of page header without segment table.
+const PAGE_HEADER_LEN: usize = 27;
+/// How many bytes to read from the end of file, to find the last granule position.
+const TAIL_SIZE: u64 = 128 * 1024;
+
+/// Header type flag of the first page of a logical stream.
+const BEGINNING_OF_STREAM: u8 = 0x02;
+/// Sample rate of opus granule positions, no matter what the input rate was.
+const OPUS_RATE: f64 = 48_000.0;
+
+struct Page<'a> {
+    header_type: u8,
+    granule_position: i64,
+    serial: u32,
+    payload: &'a [u8],
+}
+
+fn read_page(buf: &[u8], pos: usize) -> Option<(Page<'_>, usize)> {
+    if bytes(buf, pos, 4)? != CAPTURE_PATTERN {
+        return None;
+    }
+
+    let header_type = *buf.get(pos + 5)?;
+    let granule_position = le_i64(buf, pos + 6)?;
+    let serial = le_u32(buf, pos + 14)?;
+    let segments = *buf.get(pos + 26)? as usize;
+    let segment_table = bytes(buf, pos + PAGE_HEADER_LEN, segments)?;
+
+    let payload_start = pos + PAGE_HEADER_LEN + segments;
+    let payload_len = segment_table.iter().map(|len| *len as usize).sum();
+    let payload = bytes(buf, payload_start, payload_len)?;
+
+    let page = Page {
+        header_type,
+        granule_position,
+        serial,
+        payload,
+    };
+    Some((page, payload_start + payload_len))
+}
+
+/// Codec of logical stream, taken from its identification header.
+enum Stream {
+    Vorbis {
+        sample_rate: u32,
+    },
+    Opus {
+        pre_skip: u16,
+    },
+    Theora {
+        width: u32,
+        height: u32,
+        frame_rate_numerator: u32,
+        frame_rate_denominator: u32,
+        keyframe_granule_shift: u8,
+    },
+}
+
+impl Stream {
+    fn parse(header: &[u8]) -> Option<Stream> {
+        if bytes(header, 0, 7)? == b"\x01vorbis" {
+            let sample_rate = le_u32(header, 12)?;
+            return Some(Stream::Vorbis { sample_rate });
+        }
+
+        if bytes(header, 0, 8)? == b"OpusHead" {
+            let pre_skip = le_u16(header, 10)?;
+            return Some(Stream::Opus { pre_skip });
+        }
+
+        if bytes(header, 0, 7)? == b"\x80theora" {
+            // Picture size is stored as 24-bit numbers.
+            let width = be_u32(header, 13)? & 0x00FF_FFFF;
+            let height = be_u32(header, 16)? & 0x00FF_FFFF;
+            let frame_rate_numerator = be_u32(header, 22)?;
+            let frame_rate_denominator = be_u32(header, 26)?;
+            let keyframe_granule_shift = ((be_u16(header, 40)? >> 5) & 0x1F) as u8;
+
+            return Some(Stream::Theora {
+                width,
+                height,
+                frame_rate_numerator,
+                frame_rate_denominator,
+                keyframe_granule_shift,
+            });
+        }
+
+        None
+    }
+
+    fn codec(&self) -> &'static str {
+        match self {
+            Stream::Vorbis { .. } => "vorbis",
+            Stream::Opus { .. } => "opus",
+            Stream::Theora { .. } => "theora",
+        }
+    }
+
+    /// Time in seconds, corresponding to granule position.
+    fn time(&self, granule_position: i64) -> Option<f64> {
+        if granule_position < 0 {
+            return None;
+        }
+
+        match *self {
+            Stream::Vorbis { sample_rate } if sample_rate > 0 => {
+                Some(granule_position as f64 / f64::from(sample_rate))
+            }
+            Stream::Opus { pre_skip } => {
+                Some((granule_position - i64::from(pre_skip)).max(0) as f64 / OPUS_RATE)
+            }
+            Stream::Theora {
+                frame_rate_numerator,
+                frame_rate_denominator,
+                keyframe_granule_shift,
+                ..
+            } if frame_rate_numerator > 0 => {
+                let keyframe = granule_position >> keyframe_granule_shift;
+                let offset = granule_position & ((1 << keyframe_granule_shift) - 1);
+                let frames = (keyframe + offset) as f64;
+                Some(frames * f64::from(frame_rate_denominator) / f64::from(frame_rate_numerator))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn is_ogg(head: &[u8]) -> bool {
+    head.starts_with(CAPTURE_PATTERN)
+}
+
+/// Codecs are taken from the first pages of logical streams,
+/// duration from the last granule position at the end of file.
+pub async fn probe(reader: &mut RangeReader<'_>, head: &[u8]) -> Result<ProbeInfo, MediaError> {
+    let mut streams = HashMap::new();
+
+    let mut pos = 0;
+    while let Some((page, next)) = read_page(head, pos) {
+        if page.header_type & BEGINNING_OF_STREAM == 0 {
+            break;
+        }
+
+        if let Some(stream) = Stream::parse(page.payload) {
+            streams.insert(page.serial, stream);
+        }
+        pos = next;
+    }
+
+    if streams.is_empty() {
+        return Err(MediaError::InvalidMedia("No supported ogg streams"));
+    }
+
+    let mut info = ProbeInfo::default();
+
+    let video = streams
+        .values()
+        .find(|stream| matches!(stream, Stream::Theora { .. }));
+    let main = video.or_else(|| streams.values().next());
+    info.codec = main.map(|stream| stream.codec().to_owned());
+
+    if let Some(Stream::Theora { width, height, .. }) = video {
+        info.width = Some(*width);
+        info.height = Some(*height);
+    }
+
+    let size = match reader.size() {
+        Some(size) => size,
+        None => return Ok(info),
+    };
+
+    let tail_start = size.saturating_sub(TAIL_SIZE);
+    let tail = reader.read(tail_start, size - tail_start).await?;
+
+    // Tail most likely starts in the middle of a page, so look for the first capture pattern.
+    let mut last_positions = HashMap::new();
+    let mut pos = tail
+        .windows(CAPTURE_PATTERN.len())
+        .position(|window| window == CAPTURE_PATTERN);
+    while let Some(page_pos) = pos {
+        match read_page(&tail, page_pos) {
+            Some((page, next)) => {
+                if page.granule_position >= 0 {
+                    last_positions.insert(page.serial, page.granule_position);
+                }
+                pos = Some(next);
+            }
+            None => break,
+        }
+    }
+
+    info.duration = last_positions
+        .iter()
+        .filter_map(|(serial, granule_position)| streams.get(serial)?.time(*granule_position))
+        .fold(None, |max: Option<f64>, time| {
+            Some(max.map_or(time, |max| max.max(time)))
+        })
+        .filter(|duration| *duration > 0.0);
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Response, StubServer};
+
+    fn page(header_type: u8, granule_position: i64, serial: u32, payload: &[u8]) -> Vec<u8> {
+        let mut page = CAPTURE_PATTERN.to_vec();
+        page.push(0);
+        page.push(header_type);
+        page.extend_from_slice(&granule_position.to_le_bytes());
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&[0; 8]);
+
+        let mut segments: Vec<u8> = payload.chunks(255).map(|c| c.len() as u8).collect();
+        if payload.len() % 255 == 0 {
+            segments.push(0);
+        }
+        page.push(segments.len() as u8);
+        page.extend(segments);
+        page.extend_from_slice(payload);
+        page
+    }
+
+    fn vorbis_header(sample_rate: u32) -> Vec<u8> {
+        let mut header = b"\x01vorbis".to_vec();
+        header.resize(30, 0);
+        header[11] = 2;
+        header[12..16].copy_from_slice(&sample_rate.to_le_bytes());
+        header
+    }
+
+    fn opus_header(pre_skip: u16) -> Vec<u8> {
+        let mut header = b"OpusHead".to_vec();
+        header.resize(19, 0);
+        header[8] = 1;
+        header[9] = 2;
+        header[10..12].copy_from_slice(&pre_skip.to_le_bytes());
+        header
+    }
+
+    fn theora_header(width: u32, height: u32, fps: u32, granule_shift: u16) -> Vec<u8> {
+        let mut header = b"\x80theora".to_vec();
+        header.resize(42, 0);
+        header[14..17].copy_from_slice(&width.to_be_bytes()[1..]);
+        header[17..20].copy_from_slice(&height.to_be_bytes()[1..]);
+        header[22..26].copy_from_slice(&fps.to_be_bytes());
+        header[26..30].copy_from_slice(&1u32.to_be_bytes());
+        header[40..42].copy_from_slice(&(granule_shift << 5).to_be_bytes());
+        header
+    }
+
+    async fn probe_data(data: Vec<u8>) -> Result<ProbeInfo, MediaError> {
+        let server = StubServer::start(move |req| Response::file(req, "video/ogg", &data)).await;
+        let url = reqwest::Url::parse(&server.url("/video.ogv")).unwrap();
+        super::super::probe(&testing::client(), &url).await
+    }
+
+    #[test]
+    fn reads_pages() {
+        let data = [
+            page(BEGINNING_OF_STREAM, 0, 7, &[1; 300]),
+            page(0, 42, 7, &[]),
+        ]
+        .concat();
+
+        let (first, next) = read_page(&data, 0).unwrap();
+        assert_eq!(first.serial, 7);
+        assert_eq!(first.payload.len(), 300);
+
+        let (second, end) = read_page(&data, next).unwrap();
+        assert_eq!(second.granule_position, 42);
+        assert_eq!(end, data.len());
+
+        assert!(read_page(&data, end).is_none());
+        assert!(read_page(&data[..next - 1], 0).is_none());
+    }
+
+    #[tokio::test]
+    async fn theora_with_vorbis() {
+        let data = [
+            page(BEGINNING_OF_STREAM, 0, 1, &vorbis_header(44100)),
+            page(BEGINNING_OF_STREAM, 0, 2, &theora_header(640, 480, 25, 6)),
+            page(0, 0, 1, &[0; 2000]),
+            page(0, 0, 2, &[0; 2000]),
+            page(0, 441_000, 1, &[0; 100]),
+            // Keyframe 290 and 10 frames after it.
+            page(0, (290 << 6) | 10, 2, &[0; 100]),
+        ]
+        .concat();
+
+        let info = probe_data(data).await.unwrap();
+        assert_eq!(info.codec.as_deref(), Some("theora"));
+        assert_eq!(info.width, Some(640));
+        assert_eq!(info.height, Some(480));
+        assert_eq!(info.duration, Some(12.0));
+    }
+
+    #[tokio::test]
+    async fn opus_duration_excludes_pre_skip() {
+        let data = [
+            page(BEGINNING_OF_STREAM, 0, 1, &opus_header(312)),
+            page(0, 0, 1, &[0; 200_000]),
+            page(0, 48_000 * 5 + 312, 1, &[0; 100]),
+        ]
+        .concat();
+
+        let info = probe_data(data).await.unwrap();
+        assert_eq!(info.codec.as_deref(), Some("opus"));
+        assert_eq!(info.width, None);
+        assert_eq!(info.duration, Some(5.0));
+    }
+
+    #[tokio::test]
+    async fn unknown_streams_are_invalid() {
+        let data = page(BEGINNING_OF_STREAM, 0, 1, b"\x7fFLAC");
+
+        let err = probe_data(data).await.unwrap_err();
+        assert!(matches!(err, MediaError::InvalidMedia(_)));
+    }
+}
@@ -0,0 +1,582 @@
+use super::resolver::{Provider, VideoInfo};
+use super::{hls, probe, MediaError};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+/// How long to wait for provider's response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Extensions of media files, which browsers can play natively.
+const FILE_EXTENSIONS: &[&str] = &["mp4", "m4v", "webm", "ogg", "ogv", "mp3", "m4a", "wav"];
+
+/// Extensions of adaptive stream manifests, HLS and DASH.
+const MANIFEST_EXTENSIONS: &[&str] = &["m3u8", "mpd"];
+const HLS_EXTENSION: &str = "m3u8";
+const MAX_REDIRECTS: usize = 10;
+
+/// Client for requests to urls given by users. Redirects are followed to public hosts only.
+pub fn http_client() -> reqwest::Client {
+    let redirect = Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("Too many redirects")
+        } else if is_public_url(attempt.url()) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(redirect)
+        .build()
+        .unwrap_or_default()
+}
+
+fn is_public_ipv4(ip: &Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    // 0.0.0.0/8 is "this network", 100.64.0.0/10 is carrier-grade NAT.
+    let is_reserved = first == 0 || (first == 100 && (second & 0xc0) == 64);
+
+    !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_broadcast() || is_reserved)
+}
+
+fn is_public_ipv6(ip: &Ipv6Addr) -> bool {
+    if ip.is_loopback() || ip.is_unspecified() {
+        return false;
+    }
+
+    let first = ip.segments()[0];
+    let is_unique_local = (first & 0xfe00) == 0xfc00;
+    let is_link_local = (first & 0xffc0) == 0xfe80;
+    if is_unique_local || is_link_local {
+        return false;
+    }
+
+    // IPv4 mapped and compatible addresses
+    ip.to_ipv4().map_or(true, |ip| is_public_ipv4(&ip))
+}
+
+/// Whether url points to a public host, rather than to the server itself or its private network.
+///
+/// Only literal addresses and `localhost` are told apart, names aren't resolved.
+pub fn is_public_url(url: &Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return false,
+    };
+
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => is_public_ipv4(&ip),
+        Ok(IpAddr::V6(ip)) => is_public_ipv6(&ip),
+        Err(_) => {
+            let domain = host.trim_end_matches('.').to_lowercase();
+            domain != "localhost" && !domain.ends_with(".localhost")
+        }
+    }
+}
+
+fn extension(url: &Url) -> Option<String> {
+    let file_name = url.path_segments()?.last()?;
+    let dot = file_name.rfind('.')?;
+    Some(file_name[dot + 1..].to_lowercase())
+}
+
+/// Title made from file name, e.g. `Some%20Video.mp4` -> `Some Video`.
+fn title_from_file_name(url: &Url) -> Option<String> {
+    let file_name = url.path_segments()?.last()?;
+    let stem = match file_name.rfind('.') {
+        Some(dot) => &file_name[..dot],
+        None => file_name,
+    };
+    let title = percent_encoding::percent_decode_str(stem)
+        .decode_utf8_lossy()
+        .trim()
+        .to_owned();
+
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OEmbedResponse {
+    title: Option<String>,
+    /// Not a part of oEmbed spec, but some providers (e.g. vimeo) include it.
+    duration: Option<i32>,
+}
+
+/// Video hosting, which supports oEmbed.
+///
+/// Its videos are embedded by iframe, title (and duration, when provided) are taken
+/// from oEmbed endpoint.
+pub struct OEmbedProvider {
+    hosts: Vec<String>,
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl OEmbedProvider {
+    /// `endpoint` is an oEmbed endpoint, serving videos from `hosts`.
+    pub fn new(hosts: &[&str], endpoint: &str, client: reqwest::Client) -> OEmbedProvider {
+        OEmbedProvider {
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            endpoint: endpoint.to_owned(),
+            client,
+        }
+    }
+
+    pub fn youtube(client: reqwest::Client) -> OEmbedProvider {
+        let hosts = &[
+            "youtube.com",
+            "www.youtube.com",
+            "m.youtube.com",
+            "youtu.be",
+        ];
+        OEmbedProvider::new(hosts, "https://www.youtube.com/oembed", client)
+    }
+
+    pub fn vimeo(client: reqwest::Client) -> OEmbedProvider {
+        let hosts = &["vimeo.com", "www.vimeo.com", "player.vimeo.com"];
+        OEmbedProvider::new(hosts, "https://vimeo.com/api/oembed.json", client)
+    }
+
+    pub fn dailymotion(client: reqwest::Client) -> OEmbedProvider {
+        let hosts = &["dailymotion.com", "www.dailymotion.com", "dai.ly"];
+        OEmbedProvider::new(hosts, "https://www.dailymotion.com/services/oembed", client)
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<VideoInfo, MediaError> {
+        let res = self
+            .client
+            .get(&self.endpoint)
+            .query(&[("url", url.as_str()), ("format", "json")])
+            .send()
+            .await?;
+
+        match res.status() {
+            status if status.is_success() => (),
+            StatusCode::NOT_FOUND | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(MediaError::Unavailable)
+            }
+            status => return Err(MediaError::Request(status.to_string())),
+        }
+
+        let res = res.json::<OEmbedResponse>().await?;
+
+        Ok(VideoInfo {
+            title: res.title,
+            duration: res.duration.filter(|d| *d > 0),
+            is_iframe: true,
+            ..Default::default()
+        })
+    }
+}
+
+impl Provider for OEmbedProvider {
+    fn matches(&self, url: &Url) -> bool {
+        match url.host_str() {
+            Some(host) => self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)),
+            None => false,
+        }
+    }
+
+    fn resolve<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<VideoInfo, MediaError>> {
+        self.fetch(url).boxed()
+    }
+}
+
+/// Direct link to a media file.
+///
+/// File is probed for duration, dimensions and codec.
+/// Files, which couldn't be probed, are still accepted, but without metadata.
+pub struct FileProvider {
+    client: reqwest::Client,
+}
+
+impl FileProvider {
+    pub fn new(client: reqwest::Client) -> FileProvider {
+        FileProvider { client }
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<VideoInfo, MediaError> {
+        let mut info = VideoInfo {
+            title: title_from_file_name(url),
+            is_raw: true,
+            ..Default::default()
+        };
+
+        let probe_info = match probe(&self.client, url).await {
+            Ok(probe_info) => probe_info,
+            Err(MediaError::Unavailable) => return Err(MediaError::Unavailable),
+            Err(err) => {
+                debug!("Couldn't probe {}: {}", url, err);
+                return Ok(info);
+            }
+        };
+
+        info.duration = probe_info
+            .duration
+            .map(|duration| duration.round() as i32)
+            .filter(|duration| *duration > 0);
+        info.width = probe_info.width.map(|width| width as i32);
+        info.height = probe_info.height.map(|height| height as i32);
+        info.codec = probe_info.codec;
+
+        Ok(info)
+    }
+}
+
+impl Provider for FileProvider {
+    fn matches(&self, url: &Url) -> bool {
+        match extension(url) {
+            Some(extension) => FILE_EXTENSIONS.contains(&extension.as_str()),
+            None => false,
+        }
+    }
+
+    fn resolve<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<VideoInfo, MediaError>> {
+        self.fetch(url).boxed()
+    }
+}
+
+/// Direct link to HLS or DASH manifest.
+///
+/// HLS playlists are inspected to tell live streams from VOD.
+pub struct ManifestProvider {
+    client: reqwest::Client,
+}
+
+impl ManifestProvider {
+    pub fn new(client: reqwest::Client) -> ManifestProvider {
+        ManifestProvider { client }
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<VideoInfo, MediaError> {
+        let mut info = VideoInfo {
+            title: title_from_file_name(url),
+            is_raw: true,
+            ..Default::default()
+        };
+
+        if extension(url).as_deref() != Some(HLS_EXTENSION) {
+            return Ok(info);
+        }
+
+        let stream = match hls::inspect(&self.client, url).await {
+            Ok(stream) => stream,
+            Err(MediaError::Unavailable) => return Err(MediaError::Unavailable),
+            Err(err) => {
+                debug!("Couldn't inspect {}: {}", url, err);
+                return Ok(info);
+            }
+        };
+
+        info.is_live = stream.is_live;
+        info.duration = stream
+            .duration
+            .map(|duration| duration.round() as i32)
+            .filter(|duration| *duration > 0);
+        info.width = stream.width.map(|width| width as i32);
+        info.height = stream.height.map(|height| height as i32);
+        if !stream.bitrates.is_empty() {
+            let max = i32::max_value() as u64;
+            info.bitrates = Some(
+                stream
+                    .bitrates
+                    .iter()
+                    .map(|b| (*b).min(max) as i32)
+                    .collect(),
+            );
+        }
+
+        Ok(info)
+    }
+
+    async fn check_live(&self, url: &Url) -> Result<bool, MediaError> {
+        if extension(url).as_deref() != Some(HLS_EXTENSION) {
+            return Ok(true);
+        }
+
+        match hls::inspect(&self.client, url).await {
+            Ok(stream) => Ok(stream.is_live),
+            // Stream is gone
+            Err(MediaError::Unavailable) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Provider for ManifestProvider {
+    fn matches(&self, url: &Url) -> bool {
+        match extension(url) {
+            Some(extension) => MANIFEST_EXTENSIONS.contains(&extension.as_str()),
+            None => false,
+        }
+    }
+
+    fn resolve<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<VideoInfo, MediaError>> {
+        self.fetch(url).boxed()
+    }
+
+    fn is_still_live<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<bool, MediaError>> {
+        self.check_live(url).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Response, StubServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[tokio::test]
+    async fn oembed_custom_endpoint() {
+        let server = StubServer::start(|req| {
+            assert_eq!(req.path(), "/oembed");
+            assert!(req
+                .query()
+                .contains("url=https%3A%2F%2Fvideos.test%2Fwatch%3Fv%3D1"));
+            assert!(req.query().contains("format=json"));
+
+            Response::new(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"title": "Stub video", "duration": 42, "type": "video"}"#)
+        })
+        .await;
+        let provider =
+            OEmbedProvider::new(&["videos.test"], &server.url("/oembed"), testing::client());
+
+        assert!(provider.matches(&url("https://VIDEOS.test/watch?v=1")));
+        assert!(!provider.matches(&url("https://other.test/watch?v=1")));
+
+        let info = provider
+            .resolve(&url("https://videos.test/watch?v=1"))
+            .await
+            .unwrap();
+        assert!(info.is_iframe);
+        assert!(!info.is_raw);
+        assert_eq!(info.title.as_deref(), Some("Stub video"));
+        assert_eq!(info.duration, Some(42));
+    }
+
+    #[tokio::test]
+    async fn oembed_unavailable_video() {
+        let server = StubServer::start(|_| Response::new(404)).await;
+        let provider =
+            OEmbedProvider::new(&["videos.test"], &server.url("/oembed"), testing::client());
+
+        let result = provider
+            .resolve(&url("https://videos.test/watch?v=0"))
+            .await;
+        assert!(matches!(result, Err(MediaError::Unavailable)));
+    }
+
+    #[tokio::test]
+    async fn file_probed_by_content_not_content_type() {
+        let file = testing::mp4_file(10_000, 1280, 720, 1024);
+        let server =
+            StubServer::start(move |req| Response::file(req, "application/octet-stream", &file))
+                .await;
+        let provider = FileProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url(&server.url("/My%20Clip.mp4")))
+            .await
+            .unwrap();
+        assert!(info.is_raw);
+        assert!(!info.is_iframe);
+        assert_eq!(info.title.as_deref(), Some("My Clip"));
+        assert_eq!(info.duration, Some(10));
+        assert_eq!((info.width, info.height), (Some(1280), Some(720)));
+        assert_eq!(info.codec.as_deref(), Some("avc1"));
+    }
+
+    #[tokio::test]
+    async fn file_with_metadata_past_head() {
+        // `moov` is placed after media data, way past the head, which is read first.
+        let file = testing::mp4_file(90_500, 640, 360, 3 * 1024 * 1024);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = StubServer::start(move |req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::file(req, "video/mp4", &file)
+        })
+        .await;
+        let provider = FileProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url(&server.url("/video.mp4")))
+            .await
+            .unwrap();
+        assert_eq!(info.duration, Some(91));
+        assert_eq!((info.width, info.height), (Some(640), Some(360)));
+        // Media data is skipped, not downloaded.
+        assert!(requests.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn file_without_range_support() {
+        let file = testing::mp4_file(5_000, 320, 240, 16);
+        let server = StubServer::start(move |_| {
+            Response::new(200)
+                .header("Content-Type", "video/mp4")
+                .body(file.clone())
+        })
+        .await;
+        let provider = FileProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url(&server.url("/video.mp4")))
+            .await
+            .unwrap();
+        assert_eq!(info.duration, Some(5));
+    }
+
+    #[tokio::test]
+    async fn file_of_unknown_format_accepted_without_metadata() {
+        let server = StubServer::start(|req| {
+            Response::file(req, "text/html", b"<html><body>Not a video</body></html>")
+        })
+        .await;
+        let provider = FileProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url(&server.url("/video.webm")))
+            .await
+            .unwrap();
+        assert!(info.is_raw);
+        assert_eq!(info.title.as_deref(), Some("video"));
+        assert_eq!(info.duration, None);
+        assert_eq!(info.codec, None);
+    }
+
+    #[tokio::test]
+    async fn missing_file() {
+        let server = StubServer::start(|_| Response::new(404)).await;
+        let provider = FileProvider::new(testing::client());
+
+        let result = provider.resolve(&url(&server.url("/gone.mp4"))).await;
+        assert!(matches!(result, Err(MediaError::Unavailable)));
+    }
+
+    #[test]
+    fn file_matches_by_extension() {
+        let provider = FileProvider::new(testing::client());
+
+        assert!(provider.matches(&url("https://cdn.test/a/video.MP4?token=1")));
+        assert!(provider.matches(&url("https://cdn.test/song.ogg")));
+        assert!(!provider.matches(&url("https://cdn.test/stream.m3u8")));
+        assert!(!provider.matches(&url("https://cdn.test/watch")));
+    }
+
+    const VOD_PLAYLIST: &str = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:10\n\
+        #EXTINF:10.0,\n\
+        segment1.ts\n\
+        #EXTINF:5.5,\n\
+        segment2.ts\n\
+        #EXT-X-ENDLIST\n";
+
+    const LIVE_PLAYLIST: &str = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:6\n\
+        #EXT-X-MEDIA-SEQUENCE:120\n\
+        #EXTINF:6.0,\n\
+        segment120.ts\n";
+
+    #[tokio::test]
+    async fn manifest_of_ended_stream() {
+        let server = StubServer::start(|req| {
+            Response::file(
+                req,
+                "application/vnd.apple.mpegurl",
+                VOD_PLAYLIST.as_bytes(),
+            )
+        })
+        .await;
+        let provider = ManifestProvider::new(testing::client());
+        let manifest = url(&server.url("/show.m3u8"));
+
+        let info = provider.resolve(&manifest).await.unwrap();
+        assert!(info.is_raw);
+        assert!(!info.is_live);
+        assert_eq!(info.title.as_deref(), Some("show"));
+        assert_eq!(info.duration, Some(16));
+        assert!(!provider.is_still_live(&manifest).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn manifest_of_live_stream() {
+        let server = StubServer::start(|req| match req.path() {
+            "/live.m3u8" => Response::file(
+                req,
+                "application/vnd.apple.mpegurl",
+                LIVE_PLAYLIST.as_bytes(),
+            ),
+            _ => Response::new(404),
+        })
+        .await;
+        let provider = ManifestProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url(&server.url("/live.m3u8")))
+            .await
+            .unwrap();
+        assert!(info.is_raw);
+        assert!(info.is_live);
+        assert_eq!(info.duration, None);
+
+        let live = url(&server.url("/live.m3u8"));
+        assert!(provider.is_still_live(&live).await.unwrap());
+        // Stream is gone
+        let gone = url(&server.url("/ended.m3u8"));
+        assert!(!provider.is_still_live(&gone).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn manifest_variants_on_local_hosts_not_fetched() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = StubServer::start(move |req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let master = "#EXTM3U\n\
+                #EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=1280x720\n\
+                720p.m3u8\n";
+            Response::file(req, "application/vnd.apple.mpegurl", master.as_bytes())
+        })
+        .await;
+        let provider = ManifestProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url(&server.url("/master.m3u8")))
+            .await
+            .unwrap();
+        assert!(info.is_raw);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn dash_manifest_not_inspected() {
+        let provider = ManifestProvider::new(testing::client());
+
+        let info = provider
+            .resolve(&url("http://127.0.0.1:9/stream.mpd"))
+            .await
+            .unwrap();
+        assert!(info.is_raw);
+        assert!(!info.is_live);
+        assert_eq!(info.title.as_deref(), Some("stream"));
+    }
+}
@@ -0,0 +1,168 @@
+use super::providers::*;
+use super::MediaError;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::Url;
+use std::sync::Arc;
+
+/// What is known about a video behind the url.
+#[derive(Debug, Clone, Default)]
+pub struct VideoInfo {
+    pub title: Option<String>,
+    /// Duration in seconds, if known.
+    pub duration: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub codec: Option<String>,
+    /// Bitrates of stream variants in bits per second, for adaptive streams.
+    pub bitrates: Option<Vec<i32>>,
+    /// Video should be embedded by iframe of its provider.
+    pub is_iframe: bool,
+    /// Url points to a media file or a stream manifest, which are played natively.
+    pub is_raw: bool,
+    pub is_live: bool,
+}
+
+/// Source of videos, e.g. video hosting or direct links to media files.
+pub trait Provider: Send + Sync {
+    /// Whether url belongs to this provider.
+    fn matches(&self, url: &Url) -> bool;
+
+    /// Classify video and fetch its metadata.
+    fn resolve<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<VideoInfo, MediaError>>;
+
+    /// Check whether live stream is still going.
+    ///
+    /// Providers, which can't tell, assume it is.
+    fn is_still_live<'a>(&'a self, _url: &'a Url) -> BoxFuture<'a, Result<bool, MediaError>> {
+        futures::future::ok(true).boxed()
+    }
+}
+
+/// Resolves video urls, using the first provider which matches the url.
+#[derive(Clone)]
+pub struct Resolver {
+    providers: Arc<Vec<Box<dyn Provider>>>,
+}
+
+impl Resolver {
+    pub fn new(providers: Vec<Box<dyn Provider>>) -> Resolver {
+        Resolver {
+            providers: Arc::new(providers),
+        }
+    }
+
+    pub async fn resolve(&self, url: &str) -> Result<VideoInfo, MediaError> {
+        let url = Url::parse(url).map_err(|_| MediaError::InvalidUrl)?;
+
+        match self.providers.iter().find(|p| p.matches(&url)) {
+            Some(provider) => provider.resolve(&url).await,
+            None => Err(MediaError::UnsupportedUrl),
+        }
+    }
+
+    pub async fn is_still_live(&self, url: &str) -> Result<bool, MediaError> {
+        let url = Url::parse(url).map_err(|_| MediaError::InvalidUrl)?;
+
+        match self.providers.iter().find(|p| p.matches(&url)) {
+            Some(provider) => provider.is_still_live(&url).await,
+            None => Err(MediaError::UnsupportedUrl),
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Resolver {
+        let client = http_client();
+
+        Resolver::new(vec![
+            Box::new(OEmbedProvider::youtube(client.clone())),
+            Box::new(OEmbedProvider::vimeo(client.clone())),
+            Box::new(OEmbedProvider::dailymotion(client.clone())),
+            Box::new(FileProvider::new(client.clone())),
+            Box::new(ManifestProvider::new(client)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Response, StubServer};
+
+    async fn stub_resolver() -> (Resolver, StubServer) {
+        let file = testing::mp4_file(30_000, 1920, 1080, 64);
+        let server = StubServer::start(move |req| match req.path() {
+            "/oembed" => Response::new(200).body(r#"{"title": "Embedded"}"#),
+            "/clip.mp4" => Response::file(req, "video/mp4", &file),
+            "/live.m3u8" => {
+                let playlist = "#EXTM3U\n#EXTINF:4.0,\nsegment.ts\n";
+                Response::file(req, "application/vnd.apple.mpegurl", playlist.as_bytes())
+            }
+            _ => Response::new(404),
+        })
+        .await;
+
+        let client = testing::client();
+        let endpoint = server.url("/oembed");
+        let resolver = Resolver::new(vec![
+            Box::new(OEmbedProvider::new(
+                &["videos.test"],
+                &endpoint,
+                client.clone(),
+            )),
+            Box::new(FileProvider::new(client.clone())),
+            Box::new(ManifestProvider::new(client)),
+        ]);
+
+        (resolver, server)
+    }
+
+    #[tokio::test]
+    async fn embedded_video_is_iframe() {
+        let (resolver, _server) = stub_resolver().await;
+
+        let info = resolver.resolve("https://videos.test/v/1").await.unwrap();
+        assert!(info.is_iframe);
+        assert!(!info.is_raw);
+        assert!(!info.is_live);
+        assert_eq!(info.title.as_deref(), Some("Embedded"));
+    }
+
+    #[tokio::test]
+    async fn direct_file_is_raw() {
+        let (resolver, server) = stub_resolver().await;
+
+        let info = resolver.resolve(&server.url("/clip.mp4")).await.unwrap();
+        assert!(info.is_raw);
+        assert!(!info.is_iframe);
+        assert!(!info.is_live);
+        assert_eq!(info.duration, Some(30));
+        assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+    }
+
+    #[tokio::test]
+    async fn stream_manifest_is_raw_and_maybe_live() {
+        let (resolver, server) = stub_resolver().await;
+
+        let info = resolver.resolve(&server.url("/live.m3u8")).await.unwrap();
+        assert!(info.is_raw);
+        assert!(!info.is_iframe);
+        assert!(info.is_live);
+        assert!(resolver
+            .is_still_live(&server.url("/live.m3u8"))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn unknown_urls() {
+        let (resolver, _server) = stub_resolver().await;
+
+        let result = resolver.resolve("https://example.test/watch").await;
+        assert!(matches!(result, Err(MediaError::UnsupportedUrl)));
+
+        let result = resolver.resolve("not a url").await;
+        assert!(matches!(result, Err(MediaError::InvalidUrl)));
+    }
+}
@@ -0,0 +1,518 @@
+// Subtitles parsing and conversion to WebVTT.
+//
+// Only timing and text of cues are kept. Positioning and styling are dropped,
+// except for italic, bold and underline, which WebVTT supports as is.
+
+use super::MediaError;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitlesFormat {
+    Srt,
+    Vtt,
+    /// Both SubStation Alpha and Advanced SubStation Alpha.
+    Ass,
+}
+
+impl SubtitlesFormat {
+    pub fn from_extension(ext: &str) -> Option<SubtitlesFormat> {
+        match ext.to_lowercase().as_str() {
+            "srt" => Some(SubtitlesFormat::Srt),
+            "vtt" => Some(SubtitlesFormat::Vtt),
+            "ass" | "ssa" => Some(SubtitlesFormat::Ass),
+            _ => None,
+        }
+    }
+
+    /// Guess format by content, for files without known extension.
+    pub fn detect(text: &str) -> SubtitlesFormat {
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+
+        if text.starts_with("WEBVTT") {
+            SubtitlesFormat::Vtt
+        } else if text.starts_with("[Script Info]") || text.contains("[Events]") {
+            SubtitlesFormat::Ass
+        } else {
+            SubtitlesFormat::Srt
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Cue {
+    /// Milliseconds.
+    start: u64,
+    /// Milliseconds.
+    end: u64,
+    /// Lines of WebVTT cue payload, none of them empty or containing `-->`.
+    lines: Vec<String>,
+}
+
+/// Parse `[HH:]MM:SS(.|,)fff` into milliseconds.
+///
+/// Fraction may be of any precision, e.g. ASS uses centiseconds.
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let timestamp = timestamp.trim();
+    let (clock, fraction) = match timestamp.find(|c| c == '.' || c == ',') {
+        Some(dot) => (&timestamp[..dot], &timestamp[dot + 1..]),
+        None => (timestamp, ""),
+    };
+
+    let mut seconds: u64 = 0;
+    let mut parts = 0;
+    for part in clock.split(':') {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        seconds = seconds
+            .checked_mul(60)?
+            .checked_add(part.parse::<u64>().ok()?)?;
+        parts += 1;
+    }
+    if parts < 2 || parts > 3 {
+        return None;
+    }
+
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = fraction
+        .chars()
+        .chain(std::iter::repeat('0'))
+        .take(3)
+        .collect::<String>()
+        .parse::<u64>()
+        .ok()?;
+
+    seconds.checked_mul(1000)?.checked_add(millis)
+}
+
+fn format_timestamp(millis: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Parse `start --> end [settings]` line.
+fn parse_timing(line: &str) -> Option<(u64, u64)> {
+    let arrow = line.find("-->")?;
+    let start = parse_timestamp(&line[..arrow])?;
+    let end = line[arrow + 3..].split_whitespace().next()?;
+    let end = parse_timestamp(end)?;
+
+    Some((start, end))
+}
+
+/// Markup of cue text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Markup {
+    /// HTML-like tags, and override blocks, which some files borrow from ASS.
+    Srt,
+    /// Override blocks only, `<` is just text.
+    Ass,
+    /// Tags and character references.
+    Vtt,
+}
+
+/// Turn text of a cue into WebVTT payload.
+///
+/// Override blocks and tags other than `<i>`, `<b>` and `<u>` are removed,
+/// everything else is escaped.
+fn sanitize(text: &str, markup: Markup) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let closing = match c {
+            '<' if markup != Markup::Ass => rest.find('>'),
+            '{' if markup != Markup::Vtt => rest.find('}'),
+            _ => None,
+        };
+
+        if let Some(closing) = closing {
+            if c == '<' {
+                if let Some(tag) = basic_tag(&rest[1..closing]) {
+                    result.push_str(&tag);
+                }
+            }
+            rest = &rest[closing + 1..];
+            continue;
+        }
+
+        if c == '&' && markup == Markup::Vtt {
+            if let Some(len) = char_reference_len(rest) {
+                result.push_str(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            c => result.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    result
+}
+
+/// `<i>`, `<b>` or `<u>` tag, with WebVTT classes and annotations dropped, e.g. `<i.loud>`.
+fn basic_tag(tag: &str) -> Option<String> {
+    let (slash, name) = match tag.strip_prefix('/') {
+        Some(name) => ("/", name),
+        None => ("", tag),
+    };
+    let name = name
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .next()?
+        .to_lowercase();
+
+    match name.as_str() {
+        "i" | "b" | "u" => Some(format!("<{}{}>", slash, name)),
+        _ => None,
+    }
+}
+
+/// Length of character reference at the start of `text`, e.g. `&amp;` or `&#x2014;`.
+fn char_reference_len(text: &str) -> Option<usize> {
+    let end = text.find(';')?;
+    let name = &text[1..end];
+    let is_valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#');
+
+    if is_valid {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
+
+fn payload(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim().to_owned())
+        .filter(|line| !line.is_empty() && !line.contains("-->"))
+        .collect()
+}
+
+/// Split text into blocks, separated by blank lines.
+fn blocks(text: &str) -> Vec<Vec<&str>> {
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::replace(&mut block, Vec::new()));
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+
+    blocks
+}
+
+/// Blocks without timing line, e.g. SRT counters left alone, are skipped.
+fn parse_srt(text: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+
+    for block in blocks(text) {
+        let timing = match block.iter().position(|line| line.contains("-->")) {
+            Some(timing) => timing,
+            None => continue,
+        };
+        let (start, end) = match parse_timing(block[timing]) {
+            Some(timing) => timing,
+            None => continue,
+        };
+
+        let text = block[timing + 1..].join("\n");
+        cues.push(Cue {
+            start,
+            end,
+            lines: payload(&sanitize(&text, Markup::Srt)),
+        });
+    }
+
+    cues
+}
+
+/// Cue payloads are already WebVTT, but they are shown to everyone in the room,
+/// so they are sanitized like the rest.
+fn parse_vtt(text: &str) -> Result<Vec<Cue>, MediaError> {
+    let mut blocks = blocks(text).into_iter();
+
+    let header = blocks.next().unwrap_or_default();
+    if !header
+        .first()
+        .map_or(false, |line| line.starts_with("WEBVTT"))
+    {
+        return Err(MediaError::InvalidSubtitles("Missing WEBVTT header"));
+    }
+
+    let mut cues = Vec::new();
+    for block in blocks {
+        // Identifier is optional, so timing is either the first or the second line.
+        let timing = match block.iter().take(2).position(|line| line.contains("-->")) {
+            Some(timing) => timing,
+            // NOTE, STYLE and REGION blocks.
+            None => continue,
+        };
+        let (start, end) = match parse_timing(block[timing]) {
+            Some(timing) => timing,
+            None => continue,
+        };
+
+        cues.push(Cue {
+            start,
+            end,
+            lines: payload(&sanitize(&block[timing + 1..].join("\n"), Markup::Vtt)),
+        });
+    }
+
+    Ok(cues)
+}
+
+/// Only `Dialogue` lines of `[Events]` section are read.
+fn parse_ass(text: &str) -> Result<Vec<Cue>, MediaError> {
+    let mut in_events = false;
+    let mut format: Option<Vec<String>> = None;
+    let mut cues = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[Events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        if let Some(fields) = line.strip_prefix("Format:") {
+            format = Some(
+                fields
+                    .split(',')
+                    .map(|field| field.trim().to_lowercase())
+                    .collect(),
+            );
+            continue;
+        }
+
+        let dialogue = match line.strip_prefix("Dialogue:") {
+            Some(dialogue) => dialogue,
+            None => continue,
+        };
+        let format = format
+            .as_ref()
+            .ok_or(MediaError::InvalidSubtitles("Missing format of events"))?;
+        let field = |name: &str| format.iter().position(|field| field == name);
+        let (start, end, text) = match (field("start"), field("end"), field("text")) {
+            (Some(start), Some(end), Some(text)) => (start, end, text),
+            _ => return Err(MediaError::InvalidSubtitles("Invalid format of events")),
+        };
+
+        // Text is the last field and may contain commas.
+        let values = dialogue.splitn(format.len(), ',').collect::<Vec<_>>();
+        if values.len() != format.len() {
+            continue;
+        }
+
+        let (start, end) = match (parse_timestamp(values[start]), parse_timestamp(values[end])) {
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+        let text = values[text]
+            .replace("\\N", "\n")
+            .replace("\\n", "\n")
+            .replace("\\h", " ");
+
+        cues.push(Cue {
+            start,
+            end,
+            lines: payload(&sanitize(&text, Markup::Ass)),
+        });
+    }
+
+    Ok(cues)
+}
+
+/// Validate subtitles and convert them to WebVTT.
+///
+/// Format is detected by content, if not given.
+pub fn to_vtt(format: Option<SubtitlesFormat>, text: &str) -> Result<String, MediaError> {
+    let text = text.trim_start_matches('\u{feff}');
+    let format = format.unwrap_or_else(|| SubtitlesFormat::detect(text));
+
+    let mut cues = match format {
+        SubtitlesFormat::Srt => parse_srt(text),
+        SubtitlesFormat::Vtt => parse_vtt(text)?,
+        SubtitlesFormat::Ass => parse_ass(text)?,
+    };
+
+    cues.retain(|cue| cue.start <= cue.end && !cue.lines.is_empty());
+    if cues.is_empty() {
+        return Err(MediaError::InvalidSubtitles("No cues found"));
+    }
+
+    // WebVTT requires cues to be ordered by start time, which ASS doesn't.
+    cues.sort_by_key(|cue| cue.start);
+
+    let mut vtt = String::from("WEBVTT\n");
+    for cue in cues {
+        let _ = write!(
+            vtt,
+            "\n{} --> {}\n{}\n",
+            format_timestamp(cue.start),
+            format_timestamp(cue.end),
+            cue.lines.join("\n")
+        );
+    }
+
+    Ok(vtt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(3_723_456));
+        assert_eq!(parse_timestamp("02:03.4"), Some(123_400));
+        assert_eq!(parse_timestamp("0:00:01.25"), Some(1250));
+        assert_eq!(parse_timestamp("01:02"), Some(62_000));
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("01:-2.000"), None);
+        assert_eq!(parse_timestamp("99999999999999999999:00.000"), None);
+        assert_eq!(parse_timestamp("4000000000000000:00:00.000"), None);
+        assert_eq!(parse_timestamp("999999999999999999:00:00.000"), None);
+    }
+
+    #[test]
+    fn converts_srt() {
+        let srt = "\u{feff}1\r\n\
+                   00:00:01,000 --> 00:00:02,500\r\n\
+                   <i>Hello</i> <font color=\"red\">world</font> & <script>x</script>\r\n\
+                   {\\an8}Second line\r\n\
+                   \r\n\
+                   2\r\n\
+                   00:01:00,000 --> 00:01:01,000 X1:0 X2:10\r\n\
+                   a --> b\r\n\
+                   \r\n\
+                   3\r\n\
+                   00:00:03,000 --> 00:00:04,000\r\n\
+                   1 < 2\r\n";
+
+        assert_eq!(
+            to_vtt(Some(SubtitlesFormat::Srt), srt).unwrap(),
+            "WEBVTT\n\
+             \n\
+             00:00:01.000 --> 00:00:02.500\n\
+             <i>Hello</i> world &amp; x\n\
+             Second line\n\
+             \n\
+             00:00:03.000 --> 00:00:04.000\n\
+             1 &lt; 2\n\
+             \n\
+             00:01:00.000 --> 00:01:01.000\n\
+             a --&gt; b\n"
+        );
+    }
+
+    #[test]
+    fn converts_ass() {
+        let ass = "[Script Info]\n\
+                   Title: Test\n\
+                   \n\
+                   [V4+ Styles]\n\
+                   Format: Name, Fontname\n\
+                   Style: Default,Arial\n\
+                   \n\
+                   [Events]\n\
+                   Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                   Comment: 0,0:00:00.00,0:00:09.00,Default,,0,0,0,,Not shown\n\
+                   Dialogue: 0,0:00:05.00,0:00:06.50,Default,,0,0,0,,{\\i1}Later{\\i0}, with comma\n\
+                   Dialogue: 0,0:00:01.10,0:00:02.00,Default,,0,0,0,,First\\Nsecond <b>line</b>\n\
+                   Dialogue: 0,bad,0:00:02.00,Default,,0,0,0,,Skipped\n";
+
+        assert_eq!(
+            to_vtt(None, ass).unwrap(),
+            "WEBVTT\n\
+             \n\
+             00:00:01.100 --> 00:00:02.000\n\
+             First\n\
+             second &lt;b&gt;line&lt;/b&gt;\n\
+             \n\
+             00:00:05.000 --> 00:00:06.500\n\
+             Later, with comma\n"
+        );
+    }
+
+    #[test]
+    fn ass_without_format_is_invalid() {
+        let ass = "[Events]\nDialogue: 0,0:00:05.00,0:00:06.50,Default,,0,0,0,,Text\n";
+        assert!(to_vtt(Some(SubtitlesFormat::Ass), ass).is_err());
+    }
+
+    #[test]
+    fn passes_vtt_through() {
+        let vtt = "WEBVTT - Title\n\
+                   \n\
+                   NOTE comment\n\
+                   \n\
+                   intro\n\
+                   00:01.000 --> 00:02.000 align:start\n\
+                   <v Bob>Tom &amp; <i.loud>Jerry</i> &mdash; {here}\n\
+                   \n\
+                   01:00:00.000 --> 01:00:01.000\n\
+                   <c.yellow>Last</c> <00:00:01.500>word\n";
+
+        assert_eq!(
+            to_vtt(None, vtt).unwrap(),
+            "WEBVTT\n\
+             \n\
+             00:00:01.000 --> 00:00:02.000\n\
+             Tom &amp; <i>Jerry</i> &mdash; {here}\n\
+             \n\
+             01:00:00.000 --> 01:00:01.000\n\
+             Last word\n"
+        );
+    }
+
+    #[test]
+    fn vtt_payloads_are_sanitized() {
+        let vtt = "WEBVTT\n\
+                   \n\
+                   00:01.000 --> 00:02.000\n\
+                   <script>alert(1)</script><img src=x onerror=alert(1)> & &amp <b onclick=x>bold</b>\n";
+
+        assert_eq!(
+            to_vtt(Some(SubtitlesFormat::Vtt), vtt).unwrap(),
+            "WEBVTT\n\
+             \n\
+             00:00:01.000 --> 00:00:02.000\n\
+             alert(1) &amp; &amp;amp <b>bold</b>\n"
+        );
+    }
+
+    #[test]
+    fn vtt_without_header_is_invalid() {
+        assert!(to_vtt(
+            Some(SubtitlesFormat::Vtt),
+            "00:01.000 --> 00:02.000\nText\n"
+        )
+        .is_err());
+        assert!(to_vtt(None, "WEBVTT\n\nNOTE nothing here\n").is_err());
+    }
+}
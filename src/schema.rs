@@ -52,6 +52,7 @@ table! {
         hash -> Varchar,
         ext -> Varchar,
         created_at -> Timestamp,
+        saved_at -> Timestamp,
     }
 }
 
@@ -73,6 +74,16 @@ table! {
     }
 }
 
+table! {
+    playbacks (room_id) {
+        room_id -> Varchar,
+        video_id -> Varchar,
+        position -> Int8,
+        is_paused -> Bool,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     restrains (id) {
         id -> Varchar,
@@ -98,6 +109,7 @@ table! {
         title_update -> Int4,
         path_update -> Int4,
         public_update -> Int4,
+        settings_update -> Int4,
         room_delete -> Int4,
         room_view -> Int4,
         audit_log_read -> Int4,
@@ -121,6 +133,7 @@ table! {
         video_move -> Int4,
         video_iframe -> Int4,
         video_raw -> Int4,
+        video_unlimited -> Int4,
         player_pause -> Int4,
         player_resume -> Int4,
         player_rewind -> Int4,
@@ -158,6 +171,12 @@ table! {
         created_at -> Timestamp,
         last_login -> Nullable<Timestamp>,
         deleted_at -> Nullable<Timestamp>,
+        video_limit_count -> Nullable<Int4>,
+        video_limit_duration -> Nullable<Int4>,
+        skip_threshold -> Int4,
+        is_leader_mode -> Bool,
+        repeat_mode -> Int4,
+        is_shuffle -> Bool,
     }
 }
 
@@ -166,6 +185,12 @@ table! {
         id -> Varchar,
         file_id -> Varchar,
         url -> Nullable<Varchar>,
+        video_id -> Varchar,
+        language -> Nullable<Varchar>,
+        label -> Nullable<Varchar>,
+        is_default -> Bool,
+        time_offset -> Int4,
+        created_at -> Timestamp,
     }
 }
 
@@ -198,7 +223,6 @@ table! {
     videos (id) {
         id -> Varchar,
         room_id -> Varchar,
-        subtitles_id -> Nullable<Varchar>,
         file_id -> Nullable<Varchar>,
         url -> Nullable<Varchar>,
         title -> Nullable<Varchar>,
@@ -207,6 +231,12 @@ table! {
         is_iframe -> Bool,
         is_live -> Bool,
         created_at -> Timestamp,
+        position -> Int4,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        codec -> Nullable<Varchar>,
+        bitrates -> Nullable<Array<Int4>>,
+        user_id -> Nullable<Varchar>,
     }
 }
 
@@ -221,16 +251,20 @@ joinable!(message_mentions -> messages (message_id));
 joinable!(message_mentions -> users (user_id));
 joinable!(messages -> channels (channel_id));
 joinable!(messages -> users (user_id));
+joinable!(playbacks -> rooms (room_id));
+joinable!(playbacks -> videos (video_id));
 joinable!(restrains -> channels (channel_id));
 joinable!(restrains -> users (user_id));
 joinable!(roles -> rooms (room_id));
 joinable!(room_channels -> channels (channel_id));
 joinable!(room_channels -> rooms (room_id));
 joinable!(subtitles -> files (file_id));
+joinable!(subtitles -> videos (video_id));
 joinable!(user_roles -> roles (role_id));
 joinable!(user_roles -> users (user_id));
 joinable!(users -> files (file_id));
 joinable!(videos -> rooms (room_id));
+joinable!(videos -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
     audit_logs,
@@ -241,6 +275,7 @@ allow_tables_to_appear_in_same_query!(
     files,
     message_mentions,
     messages,
+    playbacks,
     restrains,
     roles,
     room_channels,
@@ -1,6 +1,8 @@
 use crate::env;
+use crate::media;
 use crate::vars::*;
 use regex::Regex;
+use reqwest::Url;
 
 fn in_range(string: &str, min: usize, max: usize) -> bool {
     let char_count = string.chars().count();
@@ -45,3 +47,64 @@ pub fn valid_room_name(name: &str) -> bool {
 pub fn valid_room_path(username: &str) -> bool {
     in_range(&username, USERNAME_MIN_LEN, USERNAME_MAX_LEN) && matches(username, r"^[a-zA-Z0-9_]+$")
 }
+
+pub fn valid_message(content: &str) -> bool {
+    in_range(content.trim(), MESSAGE_MIN_LEN, MESSAGE_MAX_LEN)
+}
+
+/// Url of a video on a public host. Server fetches these, so it shouldn't reach its own network.
+pub fn valid_video_url(url: &str) -> bool {
+    in_range(url, 1, VIDEO_URL_MAX_LEN)
+        && matches(url, r"^https?://[^\s]+$")
+        && Url::parse(url).map_or(false, |url| media::is_public_url(&url))
+}
+
+pub fn valid_video_title(title: &str) -> bool {
+    in_range(title.trim(), 1, VIDEO_TITLE_MAX_LEN)
+}
+
+pub fn valid_subtitles_language(language: &str) -> bool {
+    in_range(language, 2, SUBTITLES_LANGUAGE_MAX_LEN) && matches(language, r"^[a-zA-Z0-9-]+$")
+}
+
+pub fn valid_subtitles_label(label: &str) -> bool {
+    in_range(label.trim(), 1, SUBTITLES_LABEL_MAX_LEN)
+}
+
+pub fn valid_emote_name(name: &str) -> bool {
+    in_range(name, EMOTE_NAME_MIN_LEN, EMOTE_NAME_MAX_LEN) && matches(name, r"^[a-zA-Z0-9_]+$")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_url_on_public_host() {
+        assert!(valid_video_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(valid_video_url("http://93.184.216.34/video.mp4"));
+        assert!(valid_video_url("https://[2606:2800:220:1::]/video.mp4"));
+    }
+
+    #[test]
+    fn video_url_on_local_host() {
+        assert!(!valid_video_url("http://localhost:8080/video.mp4"));
+        assert!(!valid_video_url("http://api.localhost./video.mp4"));
+        assert!(!valid_video_url("http://127.0.0.1/video.mp4"));
+        assert!(!valid_video_url("http://2130706433/video.mp4"));
+        assert!(!valid_video_url("http://0.0.0.0/video.mp4"));
+        assert!(!valid_video_url("http://[::1]/video.mp4"));
+        assert!(!valid_video_url("http://[::ffff:127.0.0.1]/video.mp4"));
+    }
+
+    #[test]
+    fn video_url_on_private_network() {
+        assert!(!valid_video_url("http://10.0.0.1/video.mp4"));
+        assert!(!valid_video_url("http://172.16.5.4/video.mp4"));
+        assert!(!valid_video_url("http://192.168.1.1/video.mp4"));
+        assert!(!valid_video_url("http://100.64.0.1/video.mp4"));
+        assert!(!valid_video_url("http://169.254.169.254/latest/meta-data"));
+        assert!(!valid_video_url("http://[fd00::1]/video.mp4"));
+        assert!(!valid_video_url("http://[fe80::1]/video.mp4"));
+    }
+}
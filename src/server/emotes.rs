@@ -0,0 +1,188 @@
+// Custom emotes of rooms.
+//
+// Emotes are used in chat by name, so names are unique among emotes of the room.
+// Deleted emotes keep their files and can be restored, as long as the name is still free.
+
+use super::asserts;
+use super::errors::ResponseError;
+use super::permissions::ActionType;
+use super::playlist::assert_allowed;
+use crate::db;
+use crate::diesel::prelude::PgConnection;
+use crate::storage::Files;
+
+/// Extension of the image by its signature. Only raster images are accepted,
+/// as they are shown to everyone in the room.
+fn image_ext(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("jpg")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+fn valid_name(name: &str) -> Result<(), ResponseError> {
+    if !asserts::valid_emote_name(name) {
+        return Err(ResponseError::ValidationError { field: "name" });
+    }
+
+    Ok(())
+}
+
+/// Fail, if the room already has emote with this name.
+fn assert_name_free(room: &db::Room, name: &str, conn: &PgConnection) -> Result<(), ResponseError> {
+    if db::Emote::by_name(room.id.clone(), name, conn)?.is_some() {
+        return Err(ResponseError::BadRequestMessage(
+            "Emote with this name already exists",
+        ));
+    }
+
+    Ok(())
+}
+
+fn room_emote(
+    room: &db::Room,
+    emote_id: String,
+    conn: &PgConnection,
+) -> Result<db::Emote, ResponseError> {
+    let emote = db::Emote::by_id(emote_id, conn)?;
+    if emote.room_id != room.id {
+        return Err(ResponseError::NotFound);
+    }
+
+    Ok(emote)
+}
+
+/// Emotes of the room, or deleted ones, which can be restored.
+pub fn list(
+    user: Option<&db::User>,
+    room: &db::Room,
+    deleted: bool,
+    conn: &PgConnection,
+) -> Result<Vec<db::Emote>, ResponseError> {
+    if !deleted {
+        let emotes = db::Emote::list_by_room_id(room.id.clone(), conn)?;
+        return Ok(emotes);
+    }
+
+    let message = "Not allowed to restore emotes";
+    assert_allowed(user, room, ActionType::EmoteDelete, message, conn)?;
+
+    let emotes = db::Emote::list_deleted_by_room_id(room.id.clone(), conn)?;
+    Ok(emotes)
+}
+
+/// Add emote to the room. Image should be PNG, GIF, JPEG or WebP.
+pub async fn upload(
+    files: &Files,
+    user: Option<&db::User>,
+    room: &db::Room,
+    name: &str,
+    data: &[u8],
+    conn: &PgConnection,
+) -> Result<db::Emote, ResponseError> {
+    let message = "Not allowed to add emotes";
+    assert_allowed(user, room, ActionType::EmoteCreate, message, conn)?;
+
+    valid_name(name)?;
+    assert_name_free(room, name, conn)?;
+
+    let ext = image_ext(data).ok_or(ResponseError::BadRequestMessage(
+        "Emote should be PNG, GIF, JPEG or WebP image",
+    ))?;
+
+    let file = files.save(data, ext, conn).await?;
+    let emote = db::NewEmote {
+        name,
+        file_id: file.id,
+        room_id: room.id.clone(),
+        is_global: false,
+        is_deleted: false,
+    }
+    .create(conn)?;
+
+    Ok(emote)
+}
+
+/// Image of the emote. Deleted emotes are served too, so older messages still show them.
+pub fn file(
+    room: &db::Room,
+    emote_id: String,
+    conn: &PgConnection,
+) -> Result<db::File, ResponseError> {
+    let emote = room_emote(room, emote_id, conn)?;
+    let file = db::File::by_id(emote.file_id, conn)?;
+
+    Ok(file)
+}
+
+pub fn rename(
+    user: Option<&db::User>,
+    room: &db::Room,
+    emote_id: String,
+    name: &str,
+    conn: &PgConnection,
+) -> Result<db::Emote, ResponseError> {
+    let message = "Not allowed to update emotes";
+    assert_allowed(user, room, ActionType::EmoteUpdate, message, conn)?;
+
+    valid_name(name)?;
+
+    let mut emote = room_emote(room, emote_id, conn)?;
+    if emote.is_deleted {
+        return Err(ResponseError::NotFound);
+    }
+    if emote.name == name {
+        return Ok(emote);
+    }
+    assert_name_free(room, name, conn)?;
+
+    emote.name = name.to_owned();
+    let emote = emote.update(conn)?;
+
+    Ok(emote)
+}
+
+pub fn remove(
+    user: Option<&db::User>,
+    room: &db::Room,
+    emote_id: String,
+    conn: &PgConnection,
+) -> Result<db::Emote, ResponseError> {
+    let message = "Not allowed to delete emotes";
+    assert_allowed(user, room, ActionType::EmoteDelete, message, conn)?;
+
+    let emote = room_emote(room, emote_id, conn)?;
+    if emote.is_deleted {
+        return Ok(emote);
+    }
+
+    let emote = emote.soft_delete(conn)?;
+    Ok(emote)
+}
+
+/// Bring deleted emote back under its old name.
+pub fn restore(
+    user: Option<&db::User>,
+    room: &db::Room,
+    emote_id: String,
+    conn: &PgConnection,
+) -> Result<db::Emote, ResponseError> {
+    let message = "Not allowed to restore emotes";
+    assert_allowed(user, room, ActionType::EmoteDelete, message, conn)?;
+
+    let emote = room_emote(room, emote_id, conn)?;
+    if !emote.is_deleted {
+        return Ok(emote);
+    }
+    assert_name_free(room, &emote.name, conn)?;
+
+    let emote = emote.restore(conn)?;
+    Ok(emote)
+}
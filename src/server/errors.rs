@@ -1,4 +1,6 @@
 use crate::db::DieselError;
+use crate::media::MediaError;
+use crate::storage::StorageError;
 use actix_http::ResponseBuilder;
 use actix_web::Error as ActixError;
 use actix_web::{error, http::header, http::StatusCode, HttpResponse};
@@ -22,6 +24,8 @@ pub enum ResponseError {
     ValidationError { field: &'static str },
     #[fail(display = "Access error. {}", _0)]
     AccessError(&'static str),
+    #[fail(display = "Too many requests. Retry after {} ms", retry_after)]
+    TooManyRequests { retry_after: u64 },
 }
 
 impl error::ResponseError for ResponseError {
@@ -40,6 +44,7 @@ impl error::ResponseError for ResponseError {
             ResponseError::Timeout => StatusCode::GATEWAY_TIMEOUT,
             ResponseError::ValidationError { .. } => StatusCode::BAD_REQUEST,
             ResponseError::AccessError { .. } => StatusCode::UNAUTHORIZED,
+            ResponseError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 }
@@ -57,6 +62,38 @@ impl From<DieselError> for ResponseError {
     }
 }
 
+impl From<QueryError> for ResponseError {
+    fn from(err: QueryError) -> ResponseError {
+        ResponseError::from(DieselError::from(err))
+    }
+}
+
+impl From<MediaError> for ResponseError {
+    fn from(err: MediaError) -> ResponseError {
+        match err {
+            MediaError::InvalidUrl => ResponseError::ValidationError { field: "url" },
+            MediaError::UnsupportedUrl => ResponseError::BadRequestMessage("Unsupported video url"),
+            MediaError::Unavailable => ResponseError::BadRequestMessage("Video is unavailable"),
+            MediaError::Request(err) => {
+                error!("Couldn't fetch video info: {}", err);
+                ResponseError::Timeout
+            }
+            MediaError::InvalidMedia(_) => ResponseError::BadRequestMessage("Invalid media file"),
+            MediaError::InvalidSubtitles(reason) => ResponseError::BadRequestMessage(reason),
+        }
+    }
+}
+
+impl From<StorageError> for ResponseError {
+    fn from(err: StorageError) -> ResponseError {
+        match err {
+            StorageError::NotFound => ResponseError::NotFound,
+            StorageError::Io(_) => ResponseError::InternalError,
+            StorageError::Database(err) => ResponseError::from(err),
+        }
+    }
+}
+
 impl From<ActixError> for ResponseError {
     fn from(_: ActixError) -> ResponseError {
         ResponseError::InternalError
@@ -0,0 +1,194 @@
+use super::RouteResult;
+use super::States;
+use crate::db;
+use crate::server::errors::ResponseError;
+use crate::storage::{self, Files};
+use actix_web::http::header;
+use actix_web::web::{Path, Query};
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+/// Stored files never change, so they are cached for as long as possible.
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+#[derive(Deserialize, Debug)]
+pub struct FileUrl {
+    file_id: String,
+}
+
+/// Respond with stored file, or with `304 Not Modified` if client has it cached already.
+pub async fn serve(req: &HttpRequest, files: &Files, file: &db::File) -> RouteResult {
+    let etag = format!("\"{}\"", file.hash);
+
+    let is_cached = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    if is_cached {
+        return Ok(HttpResponse::NotModified()
+            .set_header(header::ETAG, etag)
+            .set_header(header::CACHE_CONTROL, CACHE_CONTROL)
+            .finish());
+    }
+
+    let data = files.read(file).await?;
+
+    let mut res = HttpResponse::Ok();
+    res.set_header(header::CONTENT_TYPE, storage::content_type(&file.ext))
+        .set_header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .set_header(header::ETAG, etag)
+        .set_header(header::CACHE_CONTROL, CACHE_CONTROL);
+    if !storage::is_inline(&file.ext) {
+        res.set_header(header::CONTENT_DISPOSITION, "attachment");
+    }
+
+    Ok(res.body(data))
+}
+
+pub async fn get(info: Path<FileUrl>, req: HttpRequest, states: States) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let file = db::File::by_id(info.file_id.clone(), &conn)?;
+    serve(&req, &states.files, &file).await
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectGarbage {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Remove unreferenced files right away, or only report them in dry run. Admins only.
+pub async fn collect_garbage(
+    query: Query<CollectGarbage>,
+    states: States,
+    user: db::User,
+) -> RouteResult {
+    if !user.is_admin {
+        return Err(ResponseError::AccessError("Not allowed to collect files"));
+    }
+
+    let conn = states.pool.get().unwrap();
+    let report = states.files.collect_garbage(query.dry_run, &conn).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryStorage;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    fn stored_file() -> (Arc<MemoryStorage>, Files, db::File) {
+        stored_file_with_ext("png")
+    }
+
+    fn stored_file_with_ext(ext: &str) -> (Arc<MemoryStorage>, Files, db::File) {
+        let storage = Arc::new(MemoryStorage::default());
+        storage
+            .objects
+            .lock()
+            .unwrap()
+            .insert(format!("ab/abcdef.{}", ext), b"image".to_vec());
+
+        let file = db::File {
+            id: String::from("file"),
+            hash: String::from("abcdef"),
+            ext: ext.to_owned(),
+            created_at: chrono::NaiveDateTime::from_timestamp(0, 0),
+            saved_at: chrono::NaiveDateTime::from_timestamp(0, 0),
+        };
+        (storage.clone(), Files::new(storage), file)
+    }
+
+    fn header<'a>(res: &'a HttpResponse, name: header::HeaderName) -> Option<&'a str> {
+        res.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    #[tokio::test]
+    async fn serves_with_caching_headers() {
+        let (storage, files, file) = stored_file();
+        let req = TestRequest::default().to_http_request();
+
+        let res = serve(&req, &files, &file).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, header::ETAG), Some("\"abcdef\""));
+        assert_eq!(
+            header(&res, header::CACHE_CONTROL),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(header(&res, header::CONTENT_TYPE), Some("image/png"));
+        assert_eq!(
+            header(&res, header::X_CONTENT_TYPE_OPTIONS),
+            Some("nosniff")
+        );
+        assert_eq!(header(&res, header::CONTENT_DISPOSITION), None);
+        assert_eq!(storage.reads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn svg_is_downloaded() {
+        let (_, files, file) = stored_file_with_ext("svg");
+        let req = TestRequest::default().to_http_request();
+
+        let res = serve(&req, &files, &file).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, header::CONTENT_TYPE), Some("image/svg+xml"));
+        assert_eq!(
+            header(&res, header::X_CONTENT_TYPE_OPTIONS),
+            Some("nosniff")
+        );
+        assert_eq!(
+            header(&res, header::CONTENT_DISPOSITION),
+            Some("attachment")
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_files_are_not_read() {
+        let (storage, files, file) = stored_file();
+
+        for if_none_match in &["\"abcdef\"", "\"other\", \"abcdef\"", "*"] {
+            let req = TestRequest::default()
+                .header(header::IF_NONE_MATCH, *if_none_match)
+                .to_http_request();
+
+            let res = serve(&req, &files, &file).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(header(&res, header::ETAG), Some("\"abcdef\""));
+            assert_eq!(
+                header(&res, header::CACHE_CONTROL),
+                Some("public, max-age=31536000, immutable")
+            );
+        }
+        assert_eq!(storage.reads.load(Ordering::SeqCst), 0);
+
+        let req = TestRequest::default()
+            .header(header::IF_NONE_MATCH, "\"other\"")
+            .to_http_request();
+        let res = serve(&req, &files, &file).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn missing_files_are_not_found() {
+        let (storage, files, file) = stored_file();
+        storage.objects.lock().unwrap().clear();
+
+        let req = TestRequest::default().to_http_request();
+        let err = serve(&req, &files, &file).await.unwrap_err();
+        assert!(matches!(err, ResponseError::NotFound));
+    }
+}
@@ -2,17 +2,23 @@ extern crate num_cpus;
 
 use crate::db;
 use crate::env;
+use crate::media;
+use crate::storage;
 use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_web::middleware::Logger;
 use actix_web::{web, App, HttpResponse, HttpServer};
 
 pub mod asserts;
 pub mod auth;
+mod emotes;
 pub mod errors;
 pub mod extractors;
+mod files;
 pub mod helpers;
 mod permissions;
+mod playlist;
 mod rooms;
+mod subtitles;
 mod users;
 mod ws;
 use extractors::*;
@@ -25,6 +31,9 @@ type RouteResult = Result<HttpResponse, errors::ResponseError>;
 #[derive(Clone)]
 pub struct AppStates {
     pool: db::DbPool,
+    hubs: ws::Hubs,
+    resolver: media::Resolver,
+    files: storage::Files,
 }
 
 type States = web::Data<AppStates>;
@@ -33,7 +42,14 @@ type States = web::Data<AppStates>;
 pub async fn run() -> std::io::Result<()> {
     let pool = db::get_pool();
     let addr = env::APP_ADDR.clone();
-    let states = AppStates { pool: pool.clone() };
+    let states = AppStates {
+        pool: pool.clone(),
+        hubs: ws::Hubs::default(),
+        resolver: media::Resolver::default(),
+        files: storage::Files::default(),
+    };
+
+    states.files.spawn_collector(pool.clone(), *env::FILES_GC_DRY_RUN);
 
     const YEAR_IN_SECS: i64 = 60 * 60 * 24 * 365;
 
@@ -57,6 +73,39 @@ pub async fn run() -> std::io::Result<()> {
                                 web::scope("/{room_path}")
                                     .route("", web::get().to(rooms::get))
                                     .route("/ws", web::get().to(ws::index))
+                                    .route("/users", web::get().to(rooms::list_online_users))
+                                    .route("/limits", web::put().to(rooms::update_video_limits))
+                                    .route("/skip-threshold", web::put().to(rooms::update_skip_threshold))
+                                    .route("/leader-mode", web::put().to(rooms::update_leader_mode))
+                                    .route("/queue-mode", web::put().to(rooms::update_queue_mode))
+                                    .service(
+                                        web::scope("/videos")
+                                            .route("", web::get().to(rooms::videos::list))
+                                            .route("", web::post().to(rooms::videos::add))
+                                            .route("", web::delete().to(rooms::videos::clear))
+                                            .route("/export", web::get().to(rooms::videos::export))
+                                            .route("/import", web::post().to(rooms::videos::import))
+                                            .route("/shuffle", web::post().to(rooms::videos::shuffle))
+                                            .route("/{video_id}", web::delete().to(rooms::videos::remove))
+                                            .route("/{video_id}/move", web::post().to(rooms::videos::move_video))
+                                            .route("/{video_id}/subtitles", web::get().to(rooms::subtitles::list))
+                                            .route("/{video_id}/subtitles", web::post().to(rooms::subtitles::upload))
+                                    )
+                                    .service(
+                                        web::scope("/subtitles")
+                                            .route("/{subtitles_id}", web::get().to(rooms::subtitles::get))
+                                            .route("/{subtitles_id}", web::put().to(rooms::subtitles::update))
+                                            .route("/{subtitles_id}", web::delete().to(rooms::subtitles::remove))
+                                    )
+                                    .service(
+                                        web::scope("/emotes")
+                                            .route("", web::get().to(rooms::emotes::list))
+                                            .route("", web::post().to(rooms::emotes::upload))
+                                            .route("/{emote_id}", web::get().to(rooms::emotes::get))
+                                            .route("/{emote_id}", web::put().to(rooms::emotes::rename))
+                                            .route("/{emote_id}", web::delete().to(rooms::emotes::remove))
+                                            .route("/{emote_id}/restore", web::post().to(rooms::emotes::restore))
+                                    )
                                     .service(
                                         web::scope("/roles")
                                             .route("/my", web::get().to(rooms::actions::list_user_roles))
@@ -66,6 +115,11 @@ pub async fn run() -> std::io::Result<()> {
                             )
                             .route("", web::get().to(HttpResponse::Ok)),
                     )
+                    .service(
+                        web::scope("/files")
+                            .route("/gc", web::post().to(files::collect_garbage))
+                            .route("/{file_id}", web::get().to(files::get))
+                    )
                     .service(
                         web::scope("/users")
                             .route("", web::get().to(users::get))
@@ -1,9 +1,8 @@
 use crate::db;
 use crate::diesel::prelude::PgConnection;
 use db::{Role, Room, User};
-use serde_repr::*;
 
-#[derive(Deserialize_repr, Debug, Clone)]
+#[derive(Debug, Clone)]
 /// Some actions require context.
 ///
 /// e.g. we can not modify role that higher in position than highest requestor's role.
@@ -11,6 +10,8 @@ pub enum ActionType {
     ChangeTitle,
     ChangePath,
     ChangePublic,
+    /// Change playback settings of the room, e.g. queue limits.
+    ChangeSettings,
     DeleteRoom,
     PasswordCreate,
     PasswordUpdate,
@@ -25,8 +26,17 @@ pub enum ActionType {
     RoleDelete(Role),
     RoleView,
     VideoAdd,
+    VideoWatch,
     VideoDelete,
     VideoMove,
+    /// Add video, which is embedded by iframe.
+    VideoIframe,
+    /// Add video by direct link.
+    VideoRaw,
+    /// Add videos beyond room's queue limits.
+    VideoUnlimited,
+    /// Upload subtitles files.
+    SubtitlesFile,
     PlayerPause,
     PlayerResume,
     PlayerRewind,
@@ -92,6 +102,7 @@ impl AssertPermission {
                 ActionType::ChangeTitle => user_role.title_update,
                 ActionType::ChangePath => user_role.path_update,
                 ActionType::ChangePublic => user_role.public_update,
+                ActionType::ChangeSettings => user_role.settings_update,
                 ActionType::DeleteRoom => user_role.room_delete,
                 ActionType::PasswordCreate => user_role.password_create,
                 ActionType::PasswordUpdate => user_role.password_update,
@@ -116,8 +127,13 @@ impl AssertPermission {
                 }
                 ActionType::RoleView => user_role.role_view,
                 ActionType::VideoAdd => user_role.video_create,
+                ActionType::VideoWatch => user_role.video_watch,
                 ActionType::VideoDelete => user_role.video_delete,
                 ActionType::VideoMove => user_role.video_move,
+                ActionType::VideoIframe => user_role.video_iframe,
+                ActionType::VideoRaw => user_role.video_raw,
+                ActionType::VideoUnlimited => user_role.video_unlimited,
+                ActionType::SubtitlesFile => user_role.subtitles_file,
                 ActionType::PlayerPause => user_role.player_pause,
                 ActionType::PlayerResume => user_role.player_resume,
                 ActionType::PlayerRewind => user_role.player_rewind,
@@ -129,7 +145,7 @@ impl AssertPermission {
                     // TODO: remove code duplications!
                     // Possibly move to User struct.
                     let requested_role = db::helpers::get_highest_user_role(
-                        requested_user.map(|u| u.id),
+                        requested_user.as_ref().map(|u| u.id.clone()),
                         self.room.id.to_owned(),
                         &conn,
                     )?;
@@ -142,7 +158,7 @@ impl AssertPermission {
                 }
                 ActionType::UserBan(requested_user) => {
                     let requested_role = db::helpers::get_highest_user_role(
-                        requested_user.map(|u| u.id),
+                        requested_user.as_ref().map(|u| u.id.clone()),
                         self.room.id.to_owned(),
                         &conn,
                     )?;
@@ -156,7 +172,7 @@ impl AssertPermission {
                 ActionType::UserUnban => user_role.user_unban,
                 ActionType::UserTimeout(requested_user) => {
                     let requested_role = db::helpers::get_highest_user_role(
-                        requested_user.map(|u| u.id),
+                        requested_user.as_ref().map(|u| u.id.clone()),
                         self.room.id.to_owned(),
                         &conn,
                     )?;
@@ -0,0 +1,753 @@
+// Room playlist operations.
+//
+// Shared by REST handlers and room hubs, so permissions are checked the same way
+// no matter where request came from.
+
+use super::asserts;
+use super::errors::ResponseError;
+use super::permissions::{ActionType, AssertPermission};
+use crate::db;
+use crate::diesel::prelude::{Connection, PgConnection};
+use crate::media::Resolver;
+use crate::vars::{PLAYLIST_IMPORT_MAX_LEN, VIDEO_TITLE_MAX_LEN};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+
+pub use crate::db::MoveTarget;
+
+/// How many videos of imported playlist are resolved at once.
+const IMPORT_CONCURRENCY: usize = 4;
+
+/// Video, requested to be added to playlist.
+///
+/// Also used as an entry of exported playlists.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AddVideo {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Duration in seconds, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i32>,
+
+    // Filled by resolver.
+    #[serde(skip)]
+    pub is_raw: bool,
+    #[serde(skip)]
+    pub is_iframe: bool,
+    #[serde(skip)]
+    pub is_live: bool,
+    #[serde(skip)]
+    pub width: Option<i32>,
+    #[serde(skip)]
+    pub height: Option<i32>,
+    #[serde(skip)]
+    pub codec: Option<String>,
+    #[serde(skip)]
+    pub bitrates: Option<Vec<i32>>,
+}
+
+/// Format of imported and exported playlists.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    /// Extended M3U.
+    M3u,
+    /// Array of `AddVideo`.
+    Json,
+}
+
+/// Outcome of importing a single video.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedItem {
+    pub url: String,
+    pub added: bool,
+    /// Why video wasn't added.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    /// Added videos.
+    pub videos: Vec<db::Video>,
+    /// Outcome of every imported video, in the same order as they were imported.
+    pub items: Vec<ImportedItem>,
+}
+
+pub(super) fn assert_allowed(
+    user: Option<&db::User>,
+    room: &db::Room,
+    action_type: ActionType,
+    message: &'static str,
+    conn: &PgConnection,
+) -> Result<(), ResponseError> {
+    if !AssertPermission::new(user, room).is_allowed(action_type, conn)? {
+        return Err(ResponseError::AccessError(message));
+    }
+
+    Ok(())
+}
+
+/// Check that user may add `videos` at all, before they are resolved.
+///
+/// Resolving requests every url, so it's not done for users, who can't add videos anyway,
+/// and the number of videos in one request is limited.
+pub fn assert_can_add(
+    user: Option<&db::User>,
+    room: &db::Room,
+    videos: &[AddVideo],
+    conn: &PgConnection,
+) -> Result<(), ResponseError> {
+    if videos.is_empty() {
+        return Err(ResponseError::BadRequestMessage("No videos to add"));
+    }
+
+    if videos.len() > PLAYLIST_IMPORT_MAX_LEN {
+        return Err(ResponseError::BadRequestMessage("Too many videos to add"));
+    }
+
+    assert_allowed(
+        user,
+        room,
+        ActionType::VideoAdd,
+        "Not allowed to add videos",
+        conn,
+    )
+}
+
+/// Classify videos and fill in missing titles and durations.
+///
+/// Should be done before videos are added, once `assert_can_add` has passed.
+pub async fn resolve(
+    resolver: &Resolver,
+    videos: Vec<AddVideo>,
+) -> Result<Vec<AddVideo>, ResponseError> {
+    let mut resolved = Vec::new();
+    for video in videos {
+        resolved.push(resolve_video(resolver, video).await?);
+    }
+
+    Ok(resolved)
+}
+
+async fn resolve_video(
+    resolver: &Resolver,
+    mut video: AddVideo,
+) -> Result<AddVideo, ResponseError> {
+    if !asserts::valid_video_url(&video.url) {
+        return Err(ResponseError::ValidationError { field: "url" });
+    }
+
+    let info = resolver.resolve(&video.url).await?;
+
+    video.is_raw = info.is_raw;
+    video.is_iframe = info.is_iframe;
+    video.is_live = info.is_live;
+    // Raw files are probed, so duration given by client is not trusted for them.
+    video.duration = if info.is_raw {
+        info.duration
+    } else {
+        info.duration.or(video.duration)
+    };
+    video.width = info.width;
+    video.height = info.height;
+    video.codec = info.codec;
+    video.bitrates = info.bitrates;
+    if video.title.is_none() {
+        video.title = info
+            .title
+            .map(|title| title.trim().chars().take(VIDEO_TITLE_MAX_LEN).collect())
+            .filter(|title: &String| !title.is_empty());
+    }
+
+    Ok(video)
+}
+
+fn validate(video: &AddVideo) -> Result<(), ResponseError> {
+    if !asserts::valid_video_url(&video.url) {
+        return Err(ResponseError::ValidationError { field: "url" });
+    }
+
+    if let Some(title) = &video.title {
+        if !asserts::valid_video_title(title) {
+            return Err(ResponseError::ValidationError { field: "title" });
+        }
+    }
+
+    if video.duration.map_or(false, |d| d < 0) {
+        return Err(ResponseError::ValidationError { field: "duration" });
+    }
+
+    Ok(())
+}
+
+fn new_video(user: Option<&db::User>, room: &db::Room, video: AddVideo) -> db::NewVideo {
+    db::NewVideo {
+        room_id: room.id.clone(),
+        file_id: None,
+        url: Some(video.url),
+        title: video.title.map(|t| t.trim().to_owned()),
+        duration: video.duration,
+        is_raw: video.is_raw,
+        is_iframe: video.is_iframe,
+        is_live: video.is_live,
+        position: 0,
+        width: video.width,
+        height: video.height,
+        codec: video.codec,
+        bitrates: video.bitrates,
+        user_id: user.map(|u| u.id.clone()),
+    }
+}
+
+/// What user may still add to the playlist, according to room's limits.
+///
+/// Anonymous users share the same quota.
+struct Quota {
+    /// Number of videos left, `None` if unlimited.
+    count: Option<i64>,
+    /// Seconds left, `None` if unlimited.
+    duration: Option<i64>,
+}
+
+impl Quota {
+    fn new(
+        user: Option<&db::User>,
+        room: &db::Room,
+        conn: &PgConnection,
+    ) -> Result<Quota, ResponseError> {
+        let unlimited = Quota {
+            count: None,
+            duration: None,
+        };
+
+        if room.video_limit_count.is_none() && room.video_limit_duration.is_none() {
+            return Ok(unlimited);
+        }
+
+        if AssertPermission::new(user, room).is_allowed(ActionType::VideoUnlimited, conn)? {
+            return Ok(unlimited);
+        }
+
+        let stats = db::Video::queue_stats(room.id.clone(), user.map(|u| u.id.clone()), conn)?;
+
+        Ok(Quota {
+            count: room
+                .video_limit_count
+                .map(|limit| i64::from(limit) - stats.count),
+            duration: room
+                .video_limit_duration
+                .map(|limit| i64::from(limit) - stats.duration),
+        })
+    }
+
+    /// Take video's share of quota. Videos of unknown duration only count towards number of videos.
+    fn take(&mut self, video: &AddVideo) -> Result<(), ResponseError> {
+        let duration = i64::from(video.duration.unwrap_or(0));
+
+        if self.count.map_or(false, |count| count < 1) {
+            return Err(ResponseError::BadRequestMessage("Too many videos in queue"));
+        }
+
+        if self.duration.map_or(false, |left| left < duration) {
+            return Err(ResponseError::BadRequestMessage(
+                "Queued videos are too long",
+            ));
+        }
+
+        self.count = self.count.map(|count| count - 1);
+        self.duration = self.duration.map(|left| left - duration);
+
+        Ok(())
+    }
+}
+
+pub fn list(room: &db::Room, conn: &PgConnection) -> Result<Vec<db::Video>, ResponseError> {
+    let videos = db::Video::list_by_room_id(room.id.clone(), conn)?;
+    Ok(videos)
+}
+
+pub fn add(
+    user: Option<&db::User>,
+    room: &db::Room,
+    videos: Vec<AddVideo>,
+    conn: &PgConnection,
+) -> Result<Vec<db::Video>, ResponseError> {
+    assert_can_add(user, room, &videos, conn)?;
+
+    if videos.iter().any(|v| v.is_iframe) {
+        let message = "Not allowed to add iframe videos";
+        assert_allowed(user, room, ActionType::VideoIframe, message, conn)?;
+    }
+
+    if videos.iter().any(|v| v.is_raw) {
+        let message = "Not allowed to add videos by direct link";
+        assert_allowed(user, room, ActionType::VideoRaw, message, conn)?;
+    }
+
+    for video in &videos {
+        validate(video)?;
+    }
+
+    conn.transaction(|| {
+        // Quota is counted with playlist locked, so concurrent adds can't exceed it together.
+        db::lock_playlist(&room.id, conn)?;
+        let mut quota = Quota::new(user, room, conn)?;
+
+        let mut new_videos = Vec::new();
+        for video in videos {
+            quota.take(&video)?;
+            new_videos.push(new_video(user, room, video));
+        }
+
+        let videos = db::NewVideo::bulk_create(new_videos, conn)?;
+        Ok(videos)
+    })
+}
+
+/// Video of imported playlist, once resolved. Failed videos keep their url to be reported.
+pub type ResolvedItem = (String, Result<AddVideo, ResponseError>);
+
+/// Resolve videos of imported playlist, a few at a time.
+///
+/// Unlike `resolve`, failed videos don't fail the rest. Should be done once
+/// `assert_can_add` has passed.
+pub async fn resolve_each(resolver: &Resolver, videos: Vec<AddVideo>) -> Vec<ResolvedItem> {
+    stream::iter(videos)
+        .map(|video| async move {
+            let url = video.url.clone();
+            (url, resolve_video(resolver, video).await)
+        })
+        .buffered(IMPORT_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Add resolved videos in bulk.
+///
+/// Unlike `add`, invalid videos don't fail the whole import. They are skipped and
+/// reported in `ImportResult::items` instead.
+pub fn import(
+    user: Option<&db::User>,
+    room: &db::Room,
+    videos: Vec<ResolvedItem>,
+    conn: &PgConnection,
+) -> Result<ImportResult, ResponseError> {
+    assert_allowed(
+        user,
+        room,
+        ActionType::VideoAdd,
+        "Not allowed to add videos",
+        conn,
+    )?;
+
+    let can_add_iframe =
+        AssertPermission::new(user, room).is_allowed(ActionType::VideoIframe, conn)?;
+    let can_add_raw = AssertPermission::new(user, room).is_allowed(ActionType::VideoRaw, conn)?;
+
+    conn.transaction(|| {
+        // Quota is counted with playlist locked, so concurrent adds can't exceed it together.
+        db::lock_playlist(&room.id, conn)?;
+        let mut quota = Quota::new(user, room, conn)?;
+
+        let mut items = Vec::new();
+        let mut new_videos = Vec::new();
+        for (url, result) in videos {
+            let result = result.and_then(|video| {
+                if video.is_iframe && !can_add_iframe {
+                    return Err(ResponseError::AccessError(
+                        "Not allowed to add iframe videos",
+                    ));
+                }
+
+                if video.is_raw && !can_add_raw {
+                    return Err(ResponseError::AccessError(
+                        "Not allowed to add videos by direct link",
+                    ));
+                }
+
+                validate(&video)?;
+                quota.take(&video)?;
+                Ok(video)
+            });
+
+            match result {
+                Ok(video) => {
+                    new_videos.push(new_video(user, room, video));
+                    items.push(ImportedItem {
+                        url,
+                        added: true,
+                        error: None,
+                    });
+                }
+                Err(err) => items.push(ImportedItem {
+                    url,
+                    added: false,
+                    error: Some(err.to_string()),
+                }),
+            }
+        }
+
+        let videos = if new_videos.is_empty() {
+            Vec::new()
+        } else {
+            db::NewVideo::bulk_create(new_videos, conn)?
+        };
+
+        Ok(ImportResult { videos, items })
+    })
+}
+
+/// Parse imported playlist.
+pub fn parse(format: PlaylistFormat, text: &str) -> Result<Vec<AddVideo>, ResponseError> {
+    match format {
+        PlaylistFormat::Json => serde_json::from_str(text)
+            .map_err(|_| ResponseError::BadRequestMessage("Malformed playlist")),
+        PlaylistFormat::M3u => Ok(parse_m3u(text)),
+    }
+}
+
+/// Every non-comment line is a url, `#EXTINF` preceding it holds duration and title.
+fn parse_m3u(text: &str) -> Vec<AddVideo> {
+    let mut videos = Vec::new();
+    let mut extinf = None;
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if line.starts_with("#EXTINF:") {
+            extinf = Some(&line["#EXTINF:".len()..]);
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let mut video = AddVideo {
+            url: line.to_owned(),
+            ..Default::default()
+        };
+
+        // `#EXTINF:<duration> [attributes],<title>`, duration is -1 if unknown.
+        if let Some(extinf) = extinf.take() {
+            let (info, title) = match title_comma(extinf) {
+                Some(comma) => (&extinf[..comma], Some(extinf[comma + 1..].trim())),
+                None => (extinf, None),
+            };
+
+            video.duration = info
+                .split_whitespace()
+                .next()
+                .and_then(|duration| duration.parse::<f64>().ok())
+                .filter(|duration| *duration >= 0.0)
+                .map(|duration| duration.round() as i32);
+            video.title = title.filter(|t| !t.is_empty()).map(str::to_owned);
+        }
+
+        videos.push(video);
+    }
+
+    videos
+}
+
+/// Position of the comma before title. Quoted attribute values may contain commas too.
+fn title_comma(extinf: &str) -> Option<usize> {
+    let mut quoted = false;
+    extinf.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ',' if !quoted => Some(i),
+        _ => None,
+    })
+}
+
+/// Serialize playlist for export. Only videos added by url are exported.
+pub fn export(format: PlaylistFormat, videos: Vec<db::Video>) -> String {
+    let videos = videos.into_iter().filter_map(|video| {
+        Some(AddVideo {
+            url: video.url?,
+            title: video.title,
+            duration: video.duration,
+            ..Default::default()
+        })
+    });
+
+    match format {
+        PlaylistFormat::Json => {
+            let videos: Vec<AddVideo> = videos.collect();
+            serde_json::to_string(&videos).unwrap_or_default()
+        }
+        PlaylistFormat::M3u => {
+            let mut text = String::from("#EXTM3U\n");
+            for video in videos {
+                // Line breaks would start a new entry.
+                let title = video
+                    .title
+                    .unwrap_or_default()
+                    .replace(|c| c == '\r' || c == '\n', " ");
+                text.push_str(&format!(
+                    "#EXTINF:{},{}\n",
+                    video.duration.unwrap_or(-1),
+                    title
+                ));
+                text.push_str(&video.url);
+                text.push('\n');
+            }
+            text
+        }
+    }
+}
+
+pub fn remove(
+    user: Option<&db::User>,
+    room: &db::Room,
+    video_id: String,
+    conn: &PgConnection,
+) -> Result<db::Video, ResponseError> {
+    let message = "Not allowed to delete videos";
+    assert_allowed(user, room, ActionType::VideoDelete, message, conn)?;
+
+    let video = db::Video::by_id(video_id, conn)?;
+    if video.room_id != room.id {
+        return Err(ResponseError::NotFound);
+    }
+
+    video.delete(conn)?;
+    Ok(video)
+}
+
+/// Move video within playlist. Returns reordered playlist.
+///
+/// `playing_id` is id of the video playing now, if any.
+pub fn move_video(
+    user: Option<&db::User>,
+    room: &db::Room,
+    video_id: String,
+    target: MoveTarget,
+    playing_id: Option<&str>,
+    conn: &PgConnection,
+) -> Result<Vec<db::Video>, ResponseError> {
+    assert_allowed(
+        user,
+        room,
+        ActionType::VideoMove,
+        "Not allowed to move videos",
+        conn,
+    )?;
+
+    let video = db::Video::by_id(video_id, conn)?;
+    if video.room_id != room.id {
+        return Err(ResponseError::NotFound);
+    }
+
+    let videos = video.move_to(target, playing_id, conn)?;
+    Ok(videos)
+}
+
+/// Shuffle playlist once. Returns shuffled playlist.
+///
+/// `playing_id` is id of the video playing now, it's kept at the start.
+pub fn shuffle(
+    user: Option<&db::User>,
+    room: &db::Room,
+    playing_id: Option<&str>,
+    conn: &PgConnection,
+) -> Result<Vec<db::Video>, ResponseError> {
+    assert_allowed(
+        user,
+        room,
+        ActionType::VideoMove,
+        "Not allowed to move videos",
+        conn,
+    )?;
+
+    let videos =
+        db::Video::shuffle_by_room_id(room.id.clone(), playing_id.map(str::to_owned), conn)?;
+    Ok(videos)
+}
+
+/// Remove every video from playlist. Returns number of removed videos.
+pub fn clear(
+    user: Option<&db::User>,
+    room: &db::Room,
+    conn: &PgConnection,
+) -> Result<usize, ResponseError> {
+    let message = "Not allowed to delete videos";
+    assert_allowed(user, room, ActionType::VideoDelete, message, conn)?;
+
+    let count = db::Video::delete_all_by_room_id(room.id.clone(), conn)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::{MediaError, Provider, VideoInfo};
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+    use reqwest::Url;
+
+    /// Provider, which resolves every url to the same info.
+    struct FixedProvider(VideoInfo);
+
+    impl Provider for FixedProvider {
+        fn matches(&self, _url: &Url) -> bool {
+            true
+        }
+
+        fn resolve<'a>(&'a self, _url: &'a Url) -> BoxFuture<'a, Result<VideoInfo, MediaError>> {
+            futures::future::ok(self.0.clone()).boxed()
+        }
+    }
+
+    async fn resolve_with(info: VideoInfo, duration: Option<i32>) -> AddVideo {
+        let resolver = Resolver::new(vec![Box::new(FixedProvider(info))]);
+        let video = AddVideo {
+            url: "https://example.com/video".to_owned(),
+            duration,
+            ..AddVideo::default()
+        };
+
+        resolve_video(&resolver, video).await.unwrap()
+    }
+
+    fn video(url: Option<&str>, title: Option<&str>, duration: Option<i32>) -> db::Video {
+        db::Video {
+            id: String::from("video"),
+            room_id: String::from("room"),
+            file_id: None,
+            url: url.map(str::to_owned),
+            title: title.map(str::to_owned),
+            duration,
+            is_raw: false,
+            is_iframe: false,
+            is_live: false,
+            created_at: chrono::NaiveDateTime::from_timestamp(0, 0),
+            position: 0,
+            width: None,
+            height: None,
+            codec: None,
+            bitrates: None,
+            user_id: None,
+        }
+    }
+
+    #[test]
+    fn parses_extinf_with_attributes() {
+        let text = "#EXTM3U\n\
+                    #EXTINF:123.6 tvg-id=\"one\" group-title=\"A, B\",First video\n\
+                    https://example.com/1.mp4\n\
+                    #EXTINF:-1,Live\n\
+                    https://example.com/live.m3u8\n";
+
+        let videos = parse_m3u(text);
+        assert_eq!(videos.len(), 2);
+        assert_eq!(videos[0].url, "https://example.com/1.mp4");
+        assert_eq!(videos[0].duration, Some(124));
+        assert_eq!(videos[0].title.as_deref(), Some("First video"));
+        assert_eq!(videos[1].url, "https://example.com/live.m3u8");
+        assert_eq!(videos[1].duration, None);
+        assert_eq!(videos[1].title.as_deref(), Some("Live"));
+    }
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        let text = "\r\n#EXTM3U\r\n\r\n#EXTINF:10,\r\n\r\n# comment\r\n  https://example.com/1.mp4  \r\n\r\nhttps://example.com/2.mp4";
+
+        let videos = parse_m3u(text);
+        assert_eq!(videos.len(), 2);
+        assert_eq!(videos[0].url, "https://example.com/1.mp4");
+        assert_eq!(videos[0].duration, Some(10));
+        assert_eq!(videos[0].title, None);
+        assert_eq!(videos[1].url, "https://example.com/2.mp4");
+        assert_eq!(videos[1].duration, None);
+    }
+
+    #[test]
+    fn keeps_relative_urls_to_be_reported() {
+        let videos = parse_m3u("#EXTINF:5,Local\nmedia/1.mp4\n../2.mp4\n");
+
+        let urls: Vec<&str> = videos.iter().map(|v| v.url.as_str()).collect();
+        assert_eq!(urls, vec!["media/1.mp4", "../2.mp4"]);
+        assert_eq!(videos[0].title.as_deref(), Some("Local"));
+        assert_eq!(videos[1].title, None);
+        assert!(!asserts::valid_video_url(&videos[0].url));
+    }
+
+    #[test]
+    fn exports_m3u() {
+        let videos = vec![
+            video(
+                Some("https://example.com/1.mp4"),
+                Some("One\r\ntwo"),
+                Some(90),
+            ),
+            video(None, Some("Uploaded"), Some(10)),
+            video(Some("https://example.com/2.mp4"), None, None),
+        ];
+
+        let text = export(PlaylistFormat::M3u, videos);
+        assert_eq!(
+            text,
+            "#EXTM3U\n\
+             #EXTINF:90,One  two\n\
+             https://example.com/1.mp4\n\
+             #EXTINF:-1,\n\
+             https://example.com/2.mp4\n"
+        );
+
+        let videos = parse_m3u(&text);
+        assert_eq!(videos[0].title.as_deref(), Some("One  two"));
+        assert_eq!(videos[0].duration, Some(90));
+        assert_eq!(videos[1].duration, None);
+    }
+
+    #[test]
+    fn exports_json() {
+        let videos = vec![
+            video(Some("https://example.com/1.mp4"), Some("One"), Some(90)),
+            video(None, Some("Uploaded"), Some(10)),
+            video(Some("https://example.com/2.mp4"), None, None),
+        ];
+
+        let text = export(PlaylistFormat::Json, videos);
+        assert_eq!(
+            text,
+            r#"[{"url":"https://example.com/1.mp4","title":"One","duration":90},{"url":"https://example.com/2.mp4"}]"#
+        );
+
+        let videos = parse(PlaylistFormat::Json, &text).unwrap();
+        assert_eq!(videos.len(), 2);
+        assert_eq!(videos[0].duration, Some(90));
+    }
+
+    #[tokio::test]
+    async fn raw_video_duration_is_not_taken_from_client() {
+        let info = VideoInfo {
+            is_raw: true,
+            ..VideoInfo::default()
+        };
+        assert_eq!(resolve_with(info, Some(600)).await.duration, None);
+
+        let info = VideoInfo {
+            is_raw: true,
+            duration: Some(90),
+            ..VideoInfo::default()
+        };
+        assert_eq!(resolve_with(info, Some(600)).await.duration, Some(90));
+    }
+
+    #[tokio::test]
+    async fn client_duration_is_kept_for_hosted_video() {
+        let info = VideoInfo {
+            is_iframe: true,
+            ..VideoInfo::default()
+        };
+        assert_eq!(resolve_with(info, Some(600)).await.duration, Some(600));
+    }
+}
@@ -0,0 +1,143 @@
+use super::read_field;
+use super::RouteResult;
+use super::States;
+use crate::db;
+use crate::server::emotes;
+use crate::server::errors::ResponseError;
+use crate::server::files;
+use crate::vars::EMOTE_MAX_SIZE;
+use actix_multipart::Multipart;
+use actix_web::web::{Json, Path, Query};
+use actix_web::{HttpRequest, HttpResponse};
+use futures::StreamExt;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct RoomUrl {
+    room_path: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmoteUrl {
+    room_path: String,
+    emote_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListEmotes {
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RenameEmote {
+    name: String,
+}
+
+/// Emotes of the room. Deleted emotes are listed instead with `?deleted=true`.
+pub async fn list(
+    info: Path<RoomUrl>,
+    query: Query<ListEmotes>,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let emotes = emotes::list(user.as_ref(), &room, query.deleted, &conn)?;
+
+    Ok(HttpResponse::Ok().json(emotes))
+}
+
+/// Upload emote image as `file` field of multipart form, named by `name` field.
+pub async fn upload(
+    info: Path<RoomUrl>,
+    mut payload: Multipart,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let mut data = None;
+    let mut name = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|_| ResponseError::BadRequest)?;
+        let field_name = match field.content_disposition() {
+            Some(disposition) => disposition.get_name().map(String::from),
+            None => continue,
+        };
+
+        match field_name.as_deref() {
+            Some("file") => data = Some(read_field(&mut field, EMOTE_MAX_SIZE).await?),
+            Some("name") => {
+                let value = read_field(&mut field, 1024).await?;
+                name = Some(String::from_utf8_lossy(&value).into_owned());
+            }
+            _ => (),
+        }
+    }
+
+    let data = data.ok_or(ResponseError::ValidationError { field: "file" })?;
+    let name = name.ok_or(ResponseError::ValidationError { field: "name" })?;
+
+    let conn = states.pool.get().unwrap();
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let emote = emotes::upload(&states.files, user.as_ref(), &room, &name, &data, &conn).await?;
+
+    states.hubs.emotes_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(emote))
+}
+
+/// Emote image.
+pub async fn get(info: Path<EmoteUrl>, req: HttpRequest, states: States) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let file = emotes::file(&room, info.emote_id.clone(), &conn)?;
+
+    files::serve(&req, &states.files, &file).await
+}
+
+pub async fn rename(
+    info: Path<EmoteUrl>,
+    json: Json<RenameEmote>,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let emote = emotes::rename(
+        user.as_ref(),
+        &room,
+        info.emote_id.clone(),
+        &json.name,
+        &conn,
+    )?;
+
+    states.hubs.emotes_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(emote))
+}
+
+pub async fn remove(info: Path<EmoteUrl>, states: States, user: Option<db::User>) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let emote = emotes::remove(user.as_ref(), &room, info.emote_id.clone(), &conn)?;
+
+    states.hubs.emotes_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(emote))
+}
+
+pub async fn restore(info: Path<EmoteUrl>, states: States, user: Option<db::User>) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let emote = emotes::restore(user.as_ref(), &room, info.emote_id.clone(), &conn)?;
+
+    states.hubs.emotes_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(emote))
+}
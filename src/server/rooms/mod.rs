@@ -3,12 +3,18 @@ use super::RouteResult;
 use super::States;
 use crate::db;
 use crate::server::errors::ResponseError;
+use crate::server::permissions::ActionType;
 use actix_identity::Identity;
+use actix_multipart::Field;
 use actix_web::web::Json;
 use actix_web::HttpResponse;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 
 pub mod actions;
+pub mod emotes;
+pub mod subtitles;
+pub mod videos;
 
 #[derive(Deserialize, Debug)]
 pub struct CreateRoom {
@@ -50,6 +56,13 @@ pub async fn create(states: States, id: Identity, form: Json<CreateRoom>) -> Rou
         }
         .create(&conn)?;
 
+        // create primary chat channel
+        let _ = db::NewRoomChannel {
+            channel_id: None,
+            room_id: room.id.clone(),
+        }
+        .create(&conn)?;
+
         // initialize default roles.
         let _ = db::NewRole::everyone(room.id.clone()).create(&conn)?;
         let _ = db::NewRole::anonymous(room.id.clone()).create(&conn)?;
@@ -77,6 +90,135 @@ pub struct Info {
     room_path: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoLimits {
+    video_limit_count: Option<i32>,
+    video_limit_duration: Option<i32>,
+}
+
+/// Set per user queue limits of the room. Omitted limits are lifted.
+pub async fn update_video_limits(
+    info: actix_web::web::Path<Info>,
+    json: Json<VideoLimits>,
+    states: States,
+    user: db::User,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    if !user.is_allowed(&room, ActionType::ChangeSettings, &conn)? {
+        return Err(ResponseError::AccessError("Not allowed to change limits"));
+    }
+
+    if json.video_limit_count.map_or(false, |limit| limit < 0) {
+        return Err(ResponseError::ValidationError {
+            field: "videoLimitCount",
+        });
+    }
+
+    if json.video_limit_duration.map_or(false, |limit| limit < 0) {
+        return Err(ResponseError::ValidationError {
+            field: "videoLimitDuration",
+        });
+    }
+
+    let room =
+        room.update_video_limits(json.video_limit_count, json.video_limit_duration, &conn)?;
+
+    states.hubs.room_updated(&room);
+
+    Ok(HttpResponse::Ok().json(room))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SkipThreshold {
+    /// Percentage of present viewers.
+    threshold: i32,
+}
+
+pub async fn update_skip_threshold(
+    info: actix_web::web::Path<Info>,
+    json: Json<SkipThreshold>,
+    states: States,
+    user: db::User,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    if !user.is_allowed(&room, ActionType::ChangeSettings, &conn)? {
+        return Err(ResponseError::AccessError(
+            "Not allowed to change skip threshold",
+        ));
+    }
+
+    if json.threshold < 1 || json.threshold > 100 {
+        return Err(ResponseError::ValidationError { field: "threshold" });
+    }
+
+    let room = room.update_skip_threshold(json.threshold, &conn)?;
+
+    states.hubs.room_updated(&room);
+
+    Ok(HttpResponse::Ok().json(room))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LeaderMode {
+    enabled: bool,
+}
+
+pub async fn update_leader_mode(
+    info: actix_web::web::Path<Info>,
+    json: Json<LeaderMode>,
+    states: States,
+    user: db::User,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    if !user.is_allowed(&room, ActionType::ChangeSettings, &conn)? {
+        return Err(ResponseError::AccessError(
+            "Not allowed to change leader mode",
+        ));
+    }
+
+    let room = room.update_leader_mode(json.enabled, &conn)?;
+
+    states.hubs.room_updated(&room);
+
+    Ok(HttpResponse::Ok().json(room))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueMode {
+    repeat_mode: db::RepeatMode,
+    is_shuffle: bool,
+}
+
+pub async fn update_queue_mode(
+    info: actix_web::web::Path<Info>,
+    json: Json<QueueMode>,
+    states: States,
+    user: db::User,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    if !user.is_allowed(&room, ActionType::VideoMove, &conn)? {
+        return Err(ResponseError::AccessError(
+            "Not allowed to change queue mode",
+        ));
+    }
+
+    let room = room.update_queue_mode(json.repeat_mode, json.is_shuffle, &conn)?;
+
+    states.hubs.room_updated(&room);
+
+    Ok(HttpResponse::Ok().json(room))
+}
+
 #[derive(Serialize)]
 pub struct RoomResponse {
     #[serde(flatten)]
@@ -86,19 +228,87 @@ pub struct RoomResponse {
     online: i32,
 }
 
-pub async fn get(info: actix_web::web::Path<Info>, states: States, id: Identity) -> RouteResult {
-    let conn = states.pool.get().unwrap();
+impl RoomResponse {
+    async fn new(room: db::Room, states: &States) -> RoomResponse {
+        let presence = states.hubs.presence(&room.id).await;
 
-    let rooms = db::Room::by_path(info.room_path.clone(), &conn)?;
+        RoomResponse {
+            room,
+            playing: presence.playing,
+            online: presence.online as i32,
+        }
+    }
+}
 
-    Ok(HttpResponse::Ok().json(rooms))
+pub async fn get(info: actix_web::web::Path<Info>, states: States) -> RouteResult {
+    let room = {
+        let conn = states.pool.get().unwrap();
+        db::Room::by_path(info.room_path.clone(), &conn)?
+    };
+    let room = RoomResponse::new(room, &states).await;
+
+    Ok(HttpResponse::Ok().json(room))
 }
 
 // TODO: pagination
 pub async fn list(states: States) -> RouteResult {
-    let conn = states.pool.get().unwrap();
+    let list = {
+        let conn = states.pool.get().unwrap();
+        db::Room::list(&conn)?
+    };
 
-    let rooms = db::Room::list(&conn)?;
+    let mut rooms = Vec::new();
+    for room in list {
+        rooms.push(RoomResponse::new(room, &states).await);
+    }
 
     Ok(HttpResponse::Ok().json(rooms))
 }
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnlineUser {
+    #[serde(flatten)]
+    user: db::PublicUser,
+    /// The highest role of the user in the room
+    role: db::Role,
+}
+
+/// List users currently connected to the room.
+pub async fn list_online_users(info: actix_web::web::Path<Info>, states: States) -> RouteResult {
+    let room = {
+        let conn = states.pool.get().unwrap();
+        db::Room::by_path(info.room_path.clone(), &conn)?
+    };
+
+    // Hub may take a while to answer, so connection is taken again only to get roles.
+    let presence = states.hubs.presence(&room.id).await;
+    let conn = states.pool.get().unwrap();
+
+    let mut users = Vec::new();
+    for user in presence.users {
+        let role =
+            db::helpers::get_highest_user_role(Some(user.id.clone()), room.id.clone(), &conn)?;
+        users.push(OnlineUser {
+            user: db::PublicUser::from(&user),
+            role,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// Read multipart field, which is expected to be no bigger than `limit`.
+async fn read_field(field: &mut Field, limit: usize) -> Result<Vec<u8>, ResponseError> {
+    let mut data = Vec::new();
+
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|_| ResponseError::BadRequest)?;
+        if data.len() + chunk.len() > limit {
+            return Err(ResponseError::BadRequestMessage("Field is too big"));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(data)
+}
@@ -0,0 +1,155 @@
+use super::read_field;
+use super::RouteResult;
+use super::States;
+use crate::db;
+use crate::server::errors::ResponseError;
+use crate::server::files;
+use crate::server::subtitles::{self, TrackInfo, Upload};
+use crate::vars::SUBTITLES_MAX_SIZE;
+use actix_multipart::Multipart;
+use actix_web::web::{Json, Path};
+use actix_web::{HttpRequest, HttpResponse};
+use futures::StreamExt;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct VideoUrl {
+    room_path: String,
+    video_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SubtitlesUrl {
+    room_path: String,
+    subtitles_id: String,
+}
+
+/// Subtitle tracks of the video.
+pub async fn list(info: Path<VideoUrl>, states: States) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let tracks = subtitles::list(&room, info.video_id.clone(), &conn)?;
+
+    Ok(HttpResponse::Ok().json(tracks))
+}
+
+/// Upload subtitles as `file` field of multipart form.
+///
+/// Track is described by optional `language`, `label`, `isDefault` and `timeOffset` fields.
+pub async fn upload(
+    info: Path<VideoUrl>,
+    mut payload: Multipart,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let mut upload = Upload {
+        video_id: info.video_id.clone(),
+        ..Upload::default()
+    };
+    let mut file = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|_| ResponseError::BadRequest)?;
+        let disposition = match field.content_disposition() {
+            Some(disposition) => disposition,
+            None => continue,
+        };
+
+        if disposition.get_name() == Some("file") {
+            upload.filename = disposition.get_filename().map(String::from);
+            file = Some(read_field(&mut field, SUBTITLES_MAX_SIZE).await?);
+            continue;
+        }
+
+        let value = read_field(&mut field, 1024).await?;
+        let value = String::from_utf8_lossy(&value).into_owned();
+        match disposition.get_name() {
+            Some("language") => upload.info.language = Some(value),
+            Some("label") => upload.info.label = Some(value),
+            Some("isDefault") => {
+                let is_default = value
+                    .parse()
+                    .map_err(|_| ResponseError::ValidationError { field: "isDefault" })?;
+                upload.info.is_default = Some(is_default);
+            }
+            Some("timeOffset") => {
+                let time_offset = value.parse().map_err(|_| ResponseError::ValidationError {
+                    field: "timeOffset",
+                })?;
+                upload.info.time_offset = Some(time_offset);
+            }
+            _ => (),
+        }
+    }
+
+    upload.data = file.ok_or(ResponseError::ValidationError { field: "file" })?;
+
+    let room = {
+        let conn = states.pool.get().unwrap();
+        let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+        subtitles::assert_can_upload(user.as_ref(), &room, &mut upload, &conn)?;
+        room
+    };
+
+    let vtt = subtitles::convert(&upload)?;
+    let file = {
+        // Connection is only held by `save`, for the lock on content.
+        let conn = states.pool.get().unwrap();
+        states.files.save(vtt.as_bytes(), "vtt", &conn).await?
+    };
+
+    let conn = states.pool.get().unwrap();
+    let subtitles = subtitles::upload(user.as_ref(), &room, upload, file, &conn)?;
+
+    states.hubs.subtitles_updated(&room.id, &subtitles.video_id);
+
+    Ok(HttpResponse::Ok().json(subtitles))
+}
+
+/// Subtitle track in WebVTT.
+pub async fn get(info: Path<SubtitlesUrl>, req: HttpRequest, states: States) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let file = subtitles::file(&room, info.subtitles_id.clone(), &conn)?;
+
+    files::serve(&req, &states.files, &file).await
+}
+
+pub async fn update(
+    info: Path<SubtitlesUrl>,
+    json: Json<TrackInfo>,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let subtitles = subtitles::update(
+        user.as_ref(),
+        &room,
+        info.subtitles_id.clone(),
+        json.into_inner(),
+        &conn,
+    )?;
+
+    states.hubs.subtitles_updated(&room.id, &subtitles.video_id);
+
+    Ok(HttpResponse::Ok().json(subtitles))
+}
+
+pub async fn remove(
+    info: Path<SubtitlesUrl>,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let subtitles = subtitles::remove(user.as_ref(), &room, info.subtitles_id.clone(), &conn)?;
+
+    states.hubs.subtitles_updated(&room.id, &subtitles.video_id);
+
+    Ok(HttpResponse::Ok().json(subtitles))
+}
@@ -0,0 +1,174 @@
+use super::RouteResult;
+use super::States;
+use crate::db;
+use crate::server::playlist::{self, AddVideo, MoveTarget, PlaylistFormat};
+use actix_web::http::header;
+use actix_web::web::{Json, Path, Query};
+use actix_web::HttpResponse;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct Url {
+    room_path: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VideoUrl {
+    room_path: String,
+    video_id: String,
+}
+
+pub async fn list(info: Path<Url>, states: States) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let videos = playlist::list(&room, &conn)?;
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+pub async fn add(
+    info: Path<Url>,
+    json: Json<Vec<AddVideo>>,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let videos = json.into_inner();
+    let room = {
+        let conn = states.pool.get().unwrap();
+        let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+        playlist::assert_can_add(user.as_ref(), &room, &videos, &conn)?;
+        room
+    };
+
+    // Resolving takes a while, so connection is taken again only to add videos.
+    let videos = playlist::resolve(&states.resolver, videos).await?;
+    let conn = states.pool.get().unwrap();
+    let videos = playlist::add(user.as_ref(), &room, videos, &conn)?;
+
+    states.hubs.playlist_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Format {
+    format: PlaylistFormat,
+}
+
+pub async fn export(info: Path<Url>, query: Query<Format>, states: States) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let videos = playlist::list(&room, &conn)?;
+
+    let (content_type, extension) = match query.format {
+        PlaylistFormat::M3u => ("audio/x-mpegurl; charset=utf-8", "m3u"),
+        PlaylistFormat::Json => ("application/json", "json"),
+    };
+    let disposition = format!("attachment; filename=\"{}.{}\"", room.path, extension);
+
+    Ok(HttpResponse::Ok()
+        .set_header(header::CONTENT_TYPE, content_type)
+        .set_header(header::CONTENT_DISPOSITION, disposition)
+        .body(playlist::export(query.format, videos)))
+}
+
+/// Import playlist, sent as request body in the format of `format` query parameter.
+pub async fn import(
+    info: Path<Url>,
+    query: Query<Format>,
+    body: String,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let videos = playlist::parse(query.format, &body)?;
+    let room = {
+        let conn = states.pool.get().unwrap();
+        let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+        playlist::assert_can_add(user.as_ref(), &room, &videos, &conn)?;
+        room
+    };
+
+    // Resolving takes a while, so connection is taken again only to add videos.
+    let videos = playlist::resolve_each(&states.resolver, videos).await;
+    let conn = states.pool.get().unwrap();
+    let result = playlist::import(user.as_ref(), &room, videos, &conn)?;
+
+    if !result.videos.is_empty() {
+        states.hubs.playlist_updated(&room.id);
+    }
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MoveVideo {
+    to: MoveTarget,
+}
+
+pub async fn move_video(
+    info: Path<VideoUrl>,
+    json: Json<MoveVideo>,
+    states: States,
+    user: Option<db::User>,
+) -> RouteResult {
+    let room = {
+        let conn = states.pool.get().unwrap();
+        db::Room::by_path(info.room_path.clone(), &conn)?
+    };
+
+    // Hub may take a while to answer, so connection is taken again only to edit playlist.
+    let playing_id = states.hubs.presence(&room.id).await.video_id;
+    let conn = states.pool.get().unwrap();
+    let videos = playlist::move_video(
+        user.as_ref(),
+        &room,
+        info.video_id.clone(),
+        json.into_inner().to,
+        playing_id.as_deref(),
+        &conn,
+    )?;
+
+    states.hubs.playlist_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+pub async fn shuffle(info: Path<Url>, states: States, user: Option<db::User>) -> RouteResult {
+    let room = {
+        let conn = states.pool.get().unwrap();
+        db::Room::by_path(info.room_path.clone(), &conn)?
+    };
+
+    // Hub may take a while to answer, so connection is taken again only to edit playlist.
+    let playing_id = states.hubs.presence(&room.id).await.video_id;
+    let conn = states.pool.get().unwrap();
+    let videos = playlist::shuffle(user.as_ref(), &room, playing_id.as_deref(), &conn)?;
+
+    states.hubs.playlist_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+pub async fn remove(info: Path<VideoUrl>, states: States, user: Option<db::User>) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let video = playlist::remove(user.as_ref(), &room, info.video_id.clone(), &conn)?;
+
+    states.hubs.playlist_updated(&room.id);
+
+    Ok(HttpResponse::Ok().json(video))
+}
+
+pub async fn clear(info: Path<Url>, states: States, user: Option<db::User>) -> RouteResult {
+    let conn = states.pool.get().unwrap();
+
+    let room = db::Room::by_path(info.room_path.clone(), &conn)?;
+    let _ = playlist::clear(user.as_ref(), &room, &conn)?;
+
+    states.hubs.playlist_updated(&room.id);
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,248 @@
+// Subtitle tracks of videos.
+//
+// Uploaded subtitles are converted to WebVTT, so clients only deal with one format.
+// Timing offset is not baked into files, clients apply it, so it can be adjusted live.
+
+use super::asserts;
+use super::errors::ResponseError;
+use super::permissions::ActionType;
+use super::playlist::assert_allowed;
+use crate::db;
+use crate::diesel::prelude::PgConnection;
+use crate::media::{self, SubtitlesFormat};
+use crate::vars::{SUBTITLES_MAX_TRACKS, SUBTITLES_OFFSET_MAX};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Description of a track, given on upload and update.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackInfo {
+    pub language: Option<String>,
+    pub label: Option<String>,
+    pub is_default: Option<bool>,
+    /// Milliseconds.
+    pub time_offset: Option<i32>,
+}
+
+impl TrackInfo {
+    fn validate(&mut self) -> Result<(), ResponseError> {
+        if let Some(language) = &self.language {
+            if !asserts::valid_subtitles_language(language) {
+                return Err(ResponseError::ValidationError { field: "language" });
+            }
+        }
+
+        if let Some(label) = &self.label {
+            if !asserts::valid_subtitles_label(label) {
+                return Err(ResponseError::ValidationError { field: "label" });
+            }
+            self.label = Some(label.trim().to_owned());
+        }
+
+        if let Some(time_offset) = self.time_offset {
+            if time_offset.abs() > SUBTITLES_OFFSET_MAX {
+                return Err(ResponseError::ValidationError {
+                    field: "timeOffset",
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn room_video(
+    room: &db::Room,
+    video_id: String,
+    conn: &PgConnection,
+) -> Result<db::Video, ResponseError> {
+    let video = db::Video::by_id(video_id, conn)?;
+    if video.room_id != room.id {
+        return Err(ResponseError::NotFound);
+    }
+
+    Ok(video)
+}
+
+/// Track of the room's video.
+fn room_track(
+    room: &db::Room,
+    subtitles_id: String,
+    conn: &PgConnection,
+) -> Result<db::Subtitles, ResponseError> {
+    let subtitles = db::Subtitles::by_id(subtitles_id, conn)?;
+    let _ = room_video(room, subtitles.video_id.clone(), conn)?;
+
+    Ok(subtitles)
+}
+
+pub fn list(
+    room: &db::Room,
+    video_id: String,
+    conn: &PgConnection,
+) -> Result<Vec<db::Subtitles>, ResponseError> {
+    let video = room_video(room, video_id, conn)?;
+    let tracks = db::Subtitles::list_by_video_id(video.id, conn)?;
+
+    Ok(tracks)
+}
+
+/// Uploaded subtitles file and description of its track.
+#[derive(Debug, Default)]
+pub struct Upload {
+    pub video_id: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+    pub info: TrackInfo,
+}
+
+/// Check that the user can add another track to the video, before the file is stored.
+pub fn assert_can_upload(
+    user: Option<&db::User>,
+    room: &db::Room,
+    upload: &mut Upload,
+    conn: &PgConnection,
+) -> Result<(), ResponseError> {
+    let _ = room_tracks(user, room, upload, conn)?;
+    Ok(())
+}
+
+/// Video and its tracks, if the user can add another one.
+fn room_tracks(
+    user: Option<&db::User>,
+    room: &db::Room,
+    upload: &mut Upload,
+    conn: &PgConnection,
+) -> Result<(db::Video, Vec<db::Subtitles>), ResponseError> {
+    let message = "Not allowed to upload subtitles";
+    assert_allowed(user, room, ActionType::SubtitlesFile, message, conn)?;
+
+    upload.info.validate()?;
+
+    let video = room_video(room, upload.video_id.clone(), conn)?;
+    let tracks = db::Subtitles::list_by_video_id(video.id.clone(), conn)?;
+    if tracks.len() >= SUBTITLES_MAX_TRACKS {
+        return Err(ResponseError::BadRequestMessage("Too many subtitle tracks"));
+    }
+
+    Ok((video, tracks))
+}
+
+/// Uploaded subtitles in WebVTT, to be stored.
+///
+/// Format is taken from extension of the filename, or detected by content.
+pub fn convert(upload: &Upload) -> Result<String, ResponseError> {
+    let text = std::str::from_utf8(&upload.data)
+        .map_err(|_| ResponseError::BadRequestMessage("Subtitles should be encoded in UTF-8"))?;
+    let format = upload
+        .filename
+        .as_deref()
+        .and_then(|filename| Path::new(filename).extension())
+        .and_then(|ext| ext.to_str())
+        .and_then(SubtitlesFormat::from_extension);
+    let vtt = media::to_vtt(format, text)?;
+
+    Ok(vtt)
+}
+
+/// Add subtitle track to the video, once its `file` is stored.
+///
+/// The first track of a video is default, unless told otherwise.
+pub fn upload(
+    user: Option<&db::User>,
+    room: &db::Room,
+    mut upload: Upload,
+    file: db::File,
+    conn: &PgConnection,
+) -> Result<db::Subtitles, ResponseError> {
+    let (video, tracks) = room_tracks(user, room, &mut upload, conn)?;
+
+    let info = upload.info;
+    let subtitles = db::NewSubtitles {
+        file_id: file.id,
+        url: None,
+        video_id: video.id,
+        language: info.language,
+        label: info.label,
+        is_default: info.is_default.unwrap_or_else(|| tracks.is_empty()),
+        time_offset: info.time_offset.unwrap_or(0),
+    }
+    .create(conn)?;
+
+    Ok(subtitles)
+}
+
+/// Stored WebVTT file of the track.
+pub fn file(
+    room: &db::Room,
+    subtitles_id: String,
+    conn: &PgConnection,
+) -> Result<db::File, ResponseError> {
+    let subtitles = room_track(room, subtitles_id, conn)?;
+    let file = db::File::by_id(subtitles.file_id, conn)?;
+
+    Ok(file)
+}
+
+/// Change description of the track. Fields, which are not given, are left as is.
+pub fn update(
+    user: Option<&db::User>,
+    room: &db::Room,
+    subtitles_id: String,
+    mut info: TrackInfo,
+    conn: &PgConnection,
+) -> Result<db::Subtitles, ResponseError> {
+    let message = "Not allowed to update subtitles";
+    assert_allowed(user, room, ActionType::SubtitlesFile, message, conn)?;
+
+    info.validate()?;
+
+    let mut subtitles = room_track(room, subtitles_id, conn)?;
+    if info.language.is_some() {
+        subtitles.language = info.language;
+    }
+    if info.label.is_some() {
+        subtitles.label = info.label;
+    }
+    if let Some(is_default) = info.is_default {
+        subtitles.is_default = is_default;
+    }
+    if let Some(time_offset) = info.time_offset {
+        subtitles.time_offset = time_offset;
+    }
+
+    let subtitles = subtitles.update(conn)?;
+    Ok(subtitles)
+}
+
+/// Shift timings of the track for the whole room.
+pub fn set_offset(
+    user: Option<&db::User>,
+    room: &db::Room,
+    subtitles_id: String,
+    time_offset: i32,
+    conn: &PgConnection,
+) -> Result<db::Subtitles, ResponseError> {
+    let info = TrackInfo {
+        time_offset: Some(time_offset),
+        ..TrackInfo::default()
+    };
+
+    update(user, room, subtitles_id, info, conn)
+}
+
+pub fn remove(
+    user: Option<&db::User>,
+    room: &db::Room,
+    subtitles_id: String,
+    conn: &PgConnection,
+) -> Result<db::Subtitles, ResponseError> {
+    let message = "Not allowed to delete subtitles";
+    assert_allowed(user, room, ActionType::SubtitlesFile, message, conn)?;
+
+    let subtitles = room_track(room, subtitles_id, conn)?;
+    subtitles.delete(conn)?;
+
+    Ok(subtitles)
+}
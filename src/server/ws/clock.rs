@@ -0,0 +1,30 @@
+// Server clock, shared with clients for synchronized playback.
+//
+// Clients estimate offset of their clock to this one with `timePing`/`timePong` exchange,
+// the same way NTP does:
+//
+//     offset = ((receivedAt - clientTime) + (sentAt - clientReceivedAt)) / 2
+//     rtt = (clientReceivedAt - clientTime) - (sentAt - receivedAt)
+
+use lazy_static::lazy_static;
+use std::time::Instant;
+
+lazy_static! {
+    /// Start of the server clock.
+    static ref EPOCH: Instant = Instant::now();
+}
+
+/// Milliseconds since `EPOCH`, the moment clock was first read.
+///
+/// Clock is monotonic, unlike wall clock it never jumps.
+pub fn now() -> f64 {
+    at(Instant::now())
+}
+
+/// Server clock reading at `instant`.
+pub fn at(instant: Instant) -> f64 {
+    match instant.checked_duration_since(*EPOCH) {
+        Some(elapsed) => elapsed.as_secs_f64() * 1000.0,
+        None => 0.0,
+    }
+}
@@ -0,0 +1,234 @@
+// Room socket protocol.
+//
+// Every frame is a JSON object of form `{ "type": "...", "data": ... }`.
+// Client frames may additionally carry `requestId`, which server echoes back
+// in `ack` or `error` frame, once request is processed.
+//
+// Client should start with `hello` frame, containing protocol version it speaks.
+// Server replies with `welcome`, or with `error` followed by close if version is not supported.
+//
+// Times are measured by server's monotonic clock, see `clock` module. Client can estimate
+// its offset with `timePing` at any moment, even before handshake.
+
+use super::hub::SessionId;
+use super::player::PlayerState;
+use crate::db;
+use crate::server::errors::ResponseError;
+use crate::server::playlist::{AddVideo, MoveTarget};
+use actix_web::error::ResponseError as _;
+use serde::{Deserialize, Serialize};
+
+/// Version of the room socket protocol.
+///
+/// Should be bumped on every incompatible change of `ClientEvent` or `ServerEvent`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberInfo {
+    pub id: SessionId,
+    /// `None` if member is anonymous
+    pub user: Option<db::PublicUser>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatMessage {
+    #[serde(flatten)]
+    pub message: db::Message,
+    pub user: db::PublicUser,
+}
+
+/// Frame sent from client to server.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientFrame {
+    pub request_id: Option<u64>,
+    #[serde(flatten)]
+    pub event: ClientEvent,
+}
+
+/// Events sent from clients to server.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", content = "data", rename_all = "camelCase")]
+pub enum ClientEvent {
+    /// Handshake. Should be the first frame sent by client.
+    Hello {
+        version: u32,
+    },
+    /// Request server time. `client_time` is echoed back in `timePong`.
+    #[serde(rename_all = "camelCase")]
+    TimePing {
+        client_time: f64,
+    },
+    PlayerPause,
+    PlayerResume,
+    /// Position in milliseconds.
+    PlayerRewind {
+        position: u64,
+    },
+    /// Skip current video and play the next one.
+    PlayerSkip,
+    /// Vote to skip current video. Video is skipped once enough viewers voted.
+    PlayerVoteSkip,
+    /// Hand leadership over to another present user. See `ServerEvent::Leader`.
+    #[serde(rename_all = "camelCase")]
+    LeaderTransfer {
+        user_id: String,
+    },
+    Message {
+        content: String,
+    },
+    PlaylistAdd {
+        videos: Vec<AddVideo>,
+    },
+    #[serde(rename_all = "camelCase")]
+    PlaylistRemove {
+        video_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    PlaylistMove {
+        video_id: String,
+        to: MoveTarget,
+    },
+    PlaylistClear,
+    /// Shuffle playlist once. Current video is put at the start.
+    PlaylistShuffle,
+    /// Shift timings of a subtitle track for everyone in the room.
+    #[serde(rename_all = "camelCase")]
+    SubtitlesOffset {
+        subtitles_id: String,
+        /// Milliseconds.
+        time_offset: i32,
+    },
+}
+
+/// Kind of an error, mapped from `ResponseError`.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    InternalError,
+    BadRequest,
+    NotFound,
+    Timeout,
+    ValidationError,
+    AccessError,
+    TooManyRequests,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorFrame {
+    /// Id of the failed request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u64>,
+    pub kind: ErrorKind,
+    /// HTTP status code equivalent
+    pub status: u16,
+    pub message: String,
+    /// Milliseconds to wait before retrying the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    /// Invalid field, for validation errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+}
+
+impl ErrorFrame {
+    pub fn new(request_id: Option<u64>, err: &ResponseError) -> ErrorFrame {
+        let (kind, retry_after, field) = match *err {
+            ResponseError::InternalError => (ErrorKind::InternalError, None, None),
+            ResponseError::BadRequest => (ErrorKind::BadRequest, None, None),
+            ResponseError::BadRequestMessage(_) => (ErrorKind::BadRequest, None, None),
+            ResponseError::NotFound => (ErrorKind::NotFound, None, None),
+            ResponseError::Timeout => (ErrorKind::Timeout, None, None),
+            ResponseError::ValidationError { field } => {
+                (ErrorKind::ValidationError, None, Some(field))
+            }
+            ResponseError::AccessError(_) => (ErrorKind::AccessError, None, None),
+            ResponseError::TooManyRequests { retry_after } => {
+                (ErrorKind::TooManyRequests, Some(retry_after), None)
+            }
+        };
+
+        ErrorFrame {
+            request_id,
+            kind,
+            status: err.status_code().as_u16(),
+            message: err.to_string(),
+            retry_after,
+            field,
+        }
+    }
+}
+
+/// Events sent from server to clients.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "data", rename_all = "camelCase")]
+pub enum ServerEvent {
+    /// Successful handshake.
+    #[serde(rename_all = "camelCase")]
+    Welcome {
+        version: u32,
+        session_id: SessionId,
+    },
+    /// Request with `request_id` is processed.
+    #[serde(rename_all = "camelCase")]
+    Ack {
+        request_id: u64,
+    },
+    Error(ErrorFrame),
+    /// Reply to `timePing`.
+    #[serde(rename_all = "camelCase")]
+    TimePong {
+        client_time: f64,
+        /// Server time, when ping was received.
+        received_at: f64,
+        /// Server time, when pong was sent.
+        sent_at: f64,
+    },
+    /// List of room members. Sent upon connection.
+    Members(Vec<MemberInfo>),
+    MemberJoined(MemberInfo),
+    MemberLeft(MemberInfo),
+    Player(PlayerState),
+    /// Video, which started playing. `None` if playlist is over.
+    ///
+    /// Resets skip votes.
+    NowPlaying(Option<db::Video>),
+    /// Skip votes for current video. Sent on every vote and when number of viewers changes.
+    SkipVotes {
+        votes: usize,
+        required: usize,
+    },
+    /// User, who controls the player in leader mode. Sent upon connection and on every change.
+    ///
+    /// `None` if leader mode is off or there is no one to lead.
+    Leader(Option<db::PublicUser>),
+    /// Videos in the queue. Sent upon connection and on every change.
+    Playlist(Vec<db::Video>),
+    /// Subtitle tracks of current video. Sent upon connection, when video starts playing
+    /// and on every change of the tracks, including timing offsets.
+    ///
+    /// Viewers pick a track themselves, the default one unless they choose otherwise.
+    #[serde(rename_all = "camelCase")]
+    Subtitles {
+        video_id: String,
+        tracks: Vec<db::Subtitles>,
+    },
+    /// Emotes of the room. Sent upon connection and on every change.
+    Emotes(Vec<db::Emote>),
+    Message(ChatMessage),
+    /// Last messages of the room. Sent upon connection.
+    MessageHistory(Vec<ChatMessage>),
+}
+
+impl ServerEvent {
+    pub fn error(request_id: Option<u64>, err: &ResponseError) -> ServerEvent {
+        ServerEvent::Error(ErrorFrame::new(request_id, err))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
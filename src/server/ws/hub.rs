@@ -0,0 +1,1236 @@
+use super::events::{ChatMessage, ClientEvent, MemberInfo, ServerEvent, PROTOCOL_VERSION};
+use super::player::Player;
+use crate::db;
+use crate::media::Resolver;
+use crate::server::asserts;
+use crate::server::errors::ResponseError;
+use crate::server::permissions::{ActionType, AssertPermission};
+use crate::server::playlist;
+use crate::server::subtitles;
+use actix::prelude::*;
+use chrono::Utc;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+pub type SessionId = Uuid;
+
+/// Number of messages sent to newcomers.
+const MESSAGE_HISTORY_LIMIT: i64 = 50;
+/// How long to remember user's last message for slow mode, at least.
+/// Messages are remembered longer, if user's cooldown is longer.
+const LAST_MESSAGE_TTL: Duration = Duration::from_secs(60 * 60);
+/// How often player state is saved, to be restored after restart.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+/// How often to check whether current live stream has ended.
+const LIVE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// How long hub is kept running once everyone has left.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Serialized event, delivered to a session as is.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct Event(pub String);
+
+/// New session joined the room.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Connect {
+    pub id: SessionId,
+    pub user: Option<db::User>,
+    pub addr: Recipient<Event>,
+}
+
+/// Session left the room.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
+    pub id: SessionId,
+}
+
+/// Event received from a session.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClientMessage {
+    pub id: SessionId,
+    pub request_id: Option<u64>,
+    pub event: ClientEvent,
+}
+
+/// Playlist was changed outside of the hub.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PlaylistUpdated;
+
+/// Subtitle tracks of a video were changed outside of the hub.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubtitlesUpdated {
+    pub video_id: String,
+}
+
+/// Emotes of the room were changed outside of the hub.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct EmotesUpdated;
+
+/// Room settings were changed outside of the hub.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RoomUpdated(pub db::Room);
+
+/// Request current presence of the room.
+#[derive(Message)]
+#[rtype(result = "Presence")]
+pub struct GetPresence;
+
+/// Who is in the room and what is playing.
+#[derive(Debug, Clone, Default)]
+pub struct Presence {
+    /// Number of connected sessions, including anonymous ones.
+    pub online: usize,
+    /// Title of the current video, or its url if title is unknown.
+    pub playing: Option<String>,
+    /// Id of the current video.
+    pub video_id: Option<String>,
+    /// Connected users. Every user is listed once, no matter how many sessions they have.
+    pub users: Vec<db::User>,
+}
+
+struct Member {
+    user: Option<db::User>,
+    addr: Recipient<Event>,
+    can_read_messages: bool,
+    /// Member is allowed to watch videos, so can vote to skip them.
+    can_watch: bool,
+    joined_at: Instant,
+}
+
+impl Member {
+    /// Key of member's skip vote. Users vote once, no matter how many sessions they have.
+    fn voter(&self, id: SessionId) -> String {
+        match &self.user {
+            Some(user) => user.id.clone(),
+            None => id.to_string(),
+        }
+    }
+
+    fn info(&self, id: SessionId) -> MemberInfo {
+        MemberInfo {
+            id,
+            user: self.user.as_ref().map(db::PublicUser::from),
+        }
+    }
+}
+
+/// Why playlist is advanced.
+#[derive(Debug, Clone, Copy)]
+enum Advance {
+    /// Video has played to the end.
+    Ended,
+    /// Video was skipped or voted off.
+    Skipped,
+    /// Video is no longer available, e.g. live stream is over. It's never repeated.
+    Gone,
+}
+
+/// Room hub.
+///
+/// There is one hub per room, every websocket connection to the room registers here.
+/// Hub keeps track of room members and broadcasts events to them.
+pub struct RoomHub {
+    room: db::Room,
+    pool: db::DbPool,
+    resolver: Resolver,
+    /// Registry, which hub removes itself from once stopped being used.
+    hubs: Hubs,
+    members: HashMap<SessionId, Member>,
+    playlist: Vec<db::Video>,
+    player: Player,
+    /// Timer, which advances playlist once current video ends.
+    advance_timer: Option<SpawnHandle>,
+    /// Interval, which checks whether current live stream has ended.
+    live_check: Option<SpawnHandle>,
+    /// Timer, which stops the hub once room is left empty.
+    idle_timer: Option<SpawnHandle>,
+    /// Last saved player state: video id, position in milliseconds and pause.
+    checkpoint: Option<(Option<String>, i64, bool)>,
+    /// Voters, who want to skip current video. See `Member::voter`.
+    skip_votes: HashSet<String>,
+    /// User, who controls the player in leader mode.
+    leader: Option<db::User>,
+    /// Primary `Channel.id` of the room, where chat messages are stored.
+    channel_id: Option<String>,
+    /// Time of the last message sent by user and their cooldown then, keyed by `User.id`.
+    last_messages: HashMap<String, (Instant, Duration)>,
+}
+
+impl RoomHub {
+    pub fn new(room: db::Room, pool: db::DbPool, resolver: Resolver, hubs: Hubs) -> RoomHub {
+        RoomHub {
+            room,
+            pool,
+            resolver,
+            hubs,
+            members: HashMap::new(),
+            playlist: Vec::new(),
+            player: Player::new(None),
+            advance_timer: None,
+            live_check: None,
+            idle_timer: None,
+            checkpoint: None,
+            skip_votes: HashSet::new(),
+            leader: None,
+            channel_id: None,
+            last_messages: HashMap::new(),
+        }
+    }
+
+    fn conn(&self) -> Result<db::DbConnection, ResponseError> {
+        self.pool.get().map_err(|err| {
+            error!("Couldn't get db connection: {}", err);
+            ResponseError::InternalError
+        })
+    }
+
+    /// Send event to a single session.
+    fn send(&self, id: &SessionId, event: &ServerEvent) {
+        if let Some(member) = self.members.get(id) {
+            let _ = member.addr.do_send(Event(event.to_json()));
+        }
+    }
+
+    /// Reply to the request of a session.
+    fn reply(&self, id: &SessionId, request_id: Option<u64>, result: Result<(), ResponseError>) {
+        match result {
+            Ok(()) => {
+                if let Some(request_id) = request_id {
+                    self.send(id, &ServerEvent::Ack { request_id });
+                }
+            }
+            Err(err) => self.send(id, &ServerEvent::error(request_id, &err)),
+        }
+    }
+
+    /// Send event to every member of the room, except `skip`.
+    fn broadcast(&self, event: &ServerEvent, skip: Option<&SessionId>) {
+        let text = event.to_json();
+
+        for (id, member) in &self.members {
+            if Some(id) == skip {
+                continue;
+            }
+            let _ = member.addr.do_send(Event(text.clone()));
+        }
+    }
+
+    /// Send event to every member, who is allowed to read messages.
+    fn broadcast_to_readers(&self, event: &ServerEvent) {
+        let text = event.to_json();
+
+        for member in self.members.values().filter(|m| m.can_read_messages) {
+            let _ = member.addr.do_send(Event(text.clone()));
+        }
+    }
+
+    fn members_info(&self) -> Vec<MemberInfo> {
+        self.members
+            .iter()
+            .map(|(id, member)| member.info(*id))
+            .collect()
+    }
+
+    fn presence(&self) -> Presence {
+        let mut users: Vec<db::User> = Vec::new();
+        for user in self.members.values().filter_map(|m| m.user.as_ref()) {
+            if !users.iter().any(|u| u.id == user.id) {
+                users.push(user.clone());
+            }
+        }
+
+        let playing = self
+            .player
+            .video()
+            .and_then(|video| video.title.clone().or_else(|| video.url.clone()));
+
+        Presence {
+            online: self.members.len(),
+            playing,
+            video_id: self.player.video().map(|video| video.id.clone()),
+            users,
+        }
+    }
+
+    fn is_allowed(
+        &self,
+        user: Option<&db::User>,
+        action_type: ActionType,
+    ) -> Result<bool, ResponseError> {
+        let conn = self.conn()?;
+        let is_allowed = AssertPermission::new(user, &self.room).is_allowed(action_type, &conn)?;
+        Ok(is_allowed)
+    }
+
+    /// Check whether member is allowed to perform an action in this room.
+    fn assert_allowed(
+        &self,
+        id: &SessionId,
+        action_type: ActionType,
+        message: &'static str,
+    ) -> Result<(), ResponseError> {
+        let user = self.member_user(id);
+
+        if !self.is_allowed(user, action_type)? {
+            return Err(ResponseError::AccessError(message));
+        }
+
+        Ok(())
+    }
+
+    /// Reload playlist from db and notify members.
+    fn reload_playlist(&mut self, ctx: &mut Context<Self>) -> Result<(), ResponseError> {
+        let conn = self.conn()?;
+        let previous = std::mem::replace(&mut self.playlist, playlist::list(&self.room, &conn)?);
+
+        self.broadcast(&ServerEvent::Playlist(self.playlist.clone()), None);
+        self.sync_player(&previous, ctx);
+        Ok(())
+    }
+
+    /// Switch to the next video, if current one is no longer in playlist.
+    fn sync_player(&mut self, previous: &[db::Video], ctx: &mut Context<Self>) {
+        let current_id = self.player.video().map(|v| v.id.clone());
+        let is_queued = match &current_id {
+            Some(current_id) => self.playlist.iter().any(|v| &v.id == current_id),
+            None => false,
+        };
+
+        if is_queued || (current_id.is_none() && self.playlist.is_empty()) {
+            return;
+        }
+
+        let next = self.next_video(previous, current_id.as_deref());
+        self.play(next, ctx);
+    }
+
+    /// Video to play after the one with `current_id`.
+    ///
+    /// In shuffle mode it's a random video, other than current one if there is a choice.
+    /// Otherwise it's the first one, that followed current video in `previous` playlist
+    /// and is still queued. Falls back to the start of playlist.
+    fn next_video(&self, previous: &[db::Video], current_id: Option<&str>) -> Option<db::Video> {
+        if self.room.is_shuffle {
+            let others: Vec<&db::Video> = self
+                .playlist
+                .iter()
+                .filter(|v| Some(v.id.as_str()) != current_id)
+                .collect();
+
+            return others
+                .choose(&mut rand::thread_rng())
+                .copied()
+                .or_else(|| self.playlist.first())
+                .cloned();
+        }
+
+        let current_index =
+            current_id.and_then(|current_id| previous.iter().position(|v| v.id == current_id));
+        current_index
+            .and_then(|current_index| {
+                previous[current_index + 1..]
+                    .iter()
+                    .find_map(|prev| self.playlist.iter().find(|v| v.id == prev.id))
+            })
+            .or_else(|| self.playlist.first())
+            .cloned()
+    }
+
+    /// Start playing a video from the beginning.
+    fn play(&mut self, video: Option<db::Video>, ctx: &mut Context<Self>) {
+        self.skip_votes.clear();
+        self.player.set_video(video.clone());
+        self.broadcast(&ServerEvent::NowPlaying(video), None);
+        if let Some(event) = self.subtitles_event() {
+            self.broadcast(&event, None);
+        }
+        self.player_updated(ctx);
+        self.schedule_live_check(ctx);
+    }
+
+    /// Subtitle tracks of current video, if any video is playing.
+    fn subtitles_event(&self) -> Option<ServerEvent> {
+        let video_id = self.player.video()?.id.clone();
+        let conn = self.conn().ok()?;
+        let tracks = db::Subtitles::list_by_video_id(video_id.clone(), &conn).ok()?;
+
+        Some(ServerEvent::Subtitles { video_id, tracks })
+    }
+
+    /// Notify members about changed subtitle tracks, if the video is playing now.
+    fn subtitles_updated(&self, video_id: &str) {
+        if self.player.video().map(|video| video.id.as_str()) != Some(video_id) {
+            return;
+        }
+
+        if let Some(event) = self.subtitles_event() {
+            self.broadcast(&event, None);
+        }
+    }
+
+    fn emotes_event(&self) -> Option<ServerEvent> {
+        let conn = self.conn().ok()?;
+        let emotes = db::Emote::list_by_room_id(self.room.id.clone(), &conn).ok()?;
+
+        Some(ServerEvent::Emotes(emotes))
+    }
+
+    /// Notify members about player's state change and reschedule playlist advance.
+    fn player_updated(&mut self, ctx: &mut Context<Self>) {
+        self.broadcast(&ServerEvent::Player(self.player.state()), None);
+        self.schedule_advance(ctx);
+    }
+
+    /// Advance playlist once current video ends.
+    ///
+    /// Paused player, live streams and videos of unknown duration are never advanced
+    /// automatically.
+    fn schedule_advance(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.advance_timer.take() {
+            ctx.cancel_future(handle);
+        }
+
+        let remaining = match self.player.remaining() {
+            Some(remaining) => remaining,
+            None => return,
+        };
+        let video_id = match self.player.video() {
+            Some(video) => video.id.clone(),
+            None => return,
+        };
+
+        let handle = ctx.run_later(remaining, move |act, ctx| {
+            act.advance_timer = None;
+
+            // Player could be changed in the meantime.
+            if act.player.video().map(|v| &v.id) != Some(&video_id) {
+                return;
+            }
+
+            if let Err(err) = act.advance(Advance::Ended, ctx) {
+                error!(
+                    "Couldn't advance playlist of room {:?}: {}",
+                    act.room.path, err
+                );
+            }
+        });
+        self.advance_timer = Some(handle);
+    }
+
+    /// Periodically check whether current live stream has ended, and advance playlist if so.
+    fn schedule_live_check(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.live_check.take() {
+            ctx.cancel_future(handle);
+        }
+
+        let (video_id, url) = match self.player.video() {
+            Some(db::Video {
+                id,
+                url: Some(url),
+                is_live: true,
+                ..
+            }) => (id.clone(), url.clone()),
+            _ => return,
+        };
+
+        let handle = ctx.run_interval(LIVE_CHECK_INTERVAL, move |act, ctx| {
+            let resolver = act.resolver.clone();
+            let url = url.clone();
+            let checking = async move { resolver.is_still_live(&url).await };
+
+            let video_id = video_id.clone();
+            ctx.spawn(checking.into_actor(act).map(move |result, act, ctx| {
+                // Player could be changed in the meantime.
+                if act.player.video().map(|v| &v.id) != Some(&video_id) {
+                    return;
+                }
+
+                match result {
+                    Ok(true) => return,
+                    Ok(false) => (),
+                    Err(err) => {
+                        debug!(
+                            "Couldn't check live stream of room {:?}: {}",
+                            act.room.path, err
+                        );
+                        return;
+                    }
+                }
+
+                info!("Live stream in room {:?} has ended", act.room.path);
+                if let Err(err) = act.advance(Advance::Gone, ctx) {
+                    error!(
+                        "Couldn't advance playlist of room {:?}: {}",
+                        act.room.path, err
+                    );
+                }
+            }));
+        });
+        self.live_check = Some(handle);
+    }
+
+    /// Voters of members, who are allowed to watch videos.
+    fn viewers(&self) -> HashSet<String> {
+        self.members
+            .iter()
+            .filter(|(_, member)| member.can_watch)
+            .map(|(id, member)| member.voter(*id))
+            .collect()
+    }
+
+    /// Notify members about skip votes and skip current video if there are enough of them.
+    ///
+    /// Votes of viewers, who left the room, are dropped.
+    fn count_skip_votes(&mut self, ctx: &mut Context<Self>) -> Result<(), ResponseError> {
+        let viewers = self.viewers();
+        self.skip_votes.retain(|voter| viewers.contains(voter));
+
+        // Round up, but one vote is always required.
+        let threshold = self.room.skip_threshold.max(0).min(100) as usize;
+        let required = ((viewers.len() * threshold + 99) / 100).max(1);
+        let votes = self.skip_votes.len();
+        self.broadcast(&ServerEvent::SkipVotes { votes, required }, None);
+
+        if votes > 0 && votes >= required {
+            info!("Current video of room {:?} is voted off", self.room.path);
+            self.advance(Advance::Skipped, ctx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move on from current video and play the next one, according to room's repeat mode.
+    fn advance(&mut self, reason: Advance, ctx: &mut Context<Self>) -> Result<(), ResponseError> {
+        let video = match self.player.video() {
+            Some(video) => video.clone(),
+            None => return self.reload_playlist(ctx),
+        };
+        let conn = self.conn()?;
+
+        match (self.room.repeat_mode, reason) {
+            (db::RepeatMode::One, Advance::Ended) => {
+                self.play(Some(video), ctx);
+                return Ok(());
+            }
+            (db::RepeatMode::All, Advance::Ended) | (db::RepeatMode::All, Advance::Skipped) => {
+                let _ = video.move_to(db::MoveTarget::Index(usize::max_value()), None, &conn)?;
+            }
+            _ => {
+                let _ = video.delete(&conn)?;
+            }
+        }
+
+        let previous = std::mem::replace(&mut self.playlist, playlist::list(&self.room, &conn)?);
+        self.broadcast(&ServerEvent::Playlist(self.playlist.clone()), None);
+
+        let next = self.next_video(&previous, Some(&video.id));
+        self.play(next, ctx);
+        Ok(())
+    }
+
+    /// Stop the hub after `IDLE_TIMEOUT`, if nobody is in the room.
+    ///
+    /// Player state is saved once hub is stopped, and restored by the next hub of the room.
+    fn schedule_idle_stop(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.idle_timer.take() {
+            ctx.cancel_future(handle);
+        }
+        if !self.members.is_empty() {
+            return;
+        }
+
+        self.idle_timer = Some(ctx.run_later(IDLE_TIMEOUT, |act, ctx| {
+            act.idle_timer = None;
+            if act.members.is_empty() {
+                act.hubs.remove(&act.room.id, &ctx.address());
+                ctx.stop();
+            }
+        }));
+    }
+
+    /// Player, as it was before restart. Starts from the beginning of playlist, if there is
+    /// no checkpoint or its video is gone.
+    ///
+    /// Time spent offline is not accounted, so no one misses anything.
+    /// Hub is started by the first connection to the room, so that's when it's restored,
+    /// not once server starts.
+    fn restore_player(&self, conn: &db::DbConnection) -> Player {
+        let playback = match db::Playback::by_room_id(self.room.id.clone(), conn) {
+            Ok(Some(playback)) => playback,
+            _ => return Player::new(self.playlist.first().cloned()),
+        };
+
+        match self.playlist.iter().find(|v| v.id == playback.video_id) {
+            Some(video) => {
+                let position = Duration::from_millis(playback.position.max(0) as u64);
+                Player::restore(video.clone(), position, playback.is_paused)
+            }
+            None => Player::new(self.playlist.first().cloned()),
+        }
+    }
+
+    /// Save player state, unless it's unchanged since the last time.
+    fn save_checkpoint(&mut self) -> Result<(), ResponseError> {
+        let video_id = self.player.video().map(|v| v.id.clone());
+        let position = self.player.position().as_millis() as i64;
+        let checkpoint = (video_id.clone(), position, self.player.is_paused());
+        if self.checkpoint.as_ref() == Some(&checkpoint) {
+            return Ok(());
+        }
+
+        let conn = self.conn()?;
+        match video_id {
+            Some(video_id) => {
+                let _ = db::NewPlayback {
+                    room_id: self.room.id.clone(),
+                    video_id,
+                    position,
+                    is_paused: self.player.is_paused(),
+                    updated_at: Utc::now().naive_utc(),
+                }
+                .save(&conn)?;
+            }
+            None => {
+                let _ = db::Playback::delete_by_room_id(self.room.id.clone(), &conn)?;
+            }
+        }
+
+        self.checkpoint = Some(checkpoint);
+        Ok(())
+    }
+
+    fn member_user(&self, id: &SessionId) -> Option<&db::User> {
+        self.members.get(id).and_then(|m| m.user.as_ref())
+    }
+
+    /// In leader mode, only the leader controls the player, no matter what roles say.
+    fn assert_leader(&self, id: &SessionId) -> Result<(), ResponseError> {
+        if !self.room.is_leader_mode {
+            return Ok(());
+        }
+
+        let user_id = self.member_user(id).map(|u| &u.id);
+        if user_id.is_none() || user_id != self.leader.as_ref().map(|u| &u.id) {
+            return Err(ResponseError::AccessError(
+                "Only the leader controls the player",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Whether user is allowed to control the player, so can lead in leader mode.
+    ///
+    /// Checked every time, so changes of roles apply to sessions, which are already open.
+    fn can_lead(&self, user: &db::User) -> bool {
+        self.is_allowed(Some(user), ActionType::PlayerPause)
+            .unwrap_or(false)
+    }
+
+    /// Present user, who joined first among those allowed to lead.
+    fn next_leader(&self) -> Option<db::User> {
+        let mut members: Vec<&Member> = self.members.values().collect();
+        members.sort_by_key(|member| member.joined_at);
+
+        members
+            .into_iter()
+            .filter_map(|member| member.user.as_ref())
+            .find(|user| self.can_lead(user))
+            .cloned()
+    }
+
+    fn set_leader(&mut self, leader: Option<db::User>) {
+        if leader.as_ref().map(|u| &u.id) == self.leader.as_ref().map(|u| &u.id) {
+            return;
+        }
+
+        self.leader = leader;
+        self.broadcast(&self.leader_event(), None);
+    }
+
+    fn leader_event(&self) -> ServerEvent {
+        ServerEvent::Leader(self.leader.as_ref().map(db::PublicUser::from))
+    }
+
+    /// Hand leadership over to the next user, if leader has left.
+    fn sync_leader(&mut self) {
+        if !self.room.is_leader_mode {
+            return self.set_leader(None);
+        }
+
+        let is_present = match &self.leader {
+            Some(leader) => self
+                .members
+                .values()
+                .filter_map(|member| member.user.as_ref())
+                .any(|user| user.id == leader.id),
+            None => false,
+        };
+
+        if !is_present {
+            let leader = self.next_leader();
+            self.set_leader(leader);
+        }
+    }
+
+    /// Make a present user the leader.
+    ///
+    /// Allowed for the leader and for those, who may change room settings.
+    fn transfer_leadership(&mut self, id: &SessionId, user_id: &str) -> Result<(), ResponseError> {
+        if !self.room.is_leader_mode {
+            return Err(ResponseError::BadRequestMessage("Leader mode is off"));
+        }
+
+        if self.assert_leader(id).is_err() {
+            self.assert_allowed(
+                id,
+                ActionType::ChangeSettings,
+                "Not allowed to choose the leader",
+            )?;
+        }
+
+        let leader = self
+            .members
+            .values()
+            .filter_map(|member| member.user.as_ref())
+            .find(|user| user.id == user_id)
+            .ok_or(ResponseError::NotFound)?;
+
+        if !self.can_lead(leader) {
+            return Err(ResponseError::BadRequestMessage(
+                "User is not allowed to control the player",
+            ));
+        }
+
+        let leader = Some(leader.clone());
+        self.set_leader(leader);
+        Ok(())
+    }
+
+    fn assert_playing(&self) -> Result<(), ResponseError> {
+        if self.player.video().is_none() {
+            return Err(ResponseError::BadRequestMessage("Nothing is playing"));
+        }
+
+        Ok(())
+    }
+
+    fn send_message_history(&self, id: &SessionId) -> Result<(), ResponseError> {
+        let channel_id = match &self.channel_id {
+            Some(channel_id) => channel_id.clone(),
+            None => return Ok(()),
+        };
+
+        let conn = self.conn()?;
+        let history =
+            db::Message::list_last_by_channel_id(channel_id, MESSAGE_HISTORY_LIMIT, &conn)?
+                .into_iter()
+                .map(|(message, user)| ChatMessage {
+                    message,
+                    user: db::PublicUser::from(&user),
+                })
+                .collect();
+
+        self.send(id, &ServerEvent::MessageHistory(history));
+        Ok(())
+    }
+
+    /// Slow mode cooldown of the user, given by their roles.
+    fn message_cooldown(&self, user: &db::User) -> Result<Duration, ResponseError> {
+        let conn = self.conn()?;
+        let timeout = db::helpers::get_user_message_timeout(
+            Some(user.id.clone()),
+            self.room.id.clone(),
+            &conn,
+        )?;
+
+        Ok(Duration::from_secs(timeout.max(0) as u64))
+    }
+
+    /// Check that `cooldown` has passed since the last message of the user.
+    fn assert_cooldown(&self, user: &db::User, cooldown: Duration) -> Result<(), ResponseError> {
+        let last_message_at = match self.last_messages.get(&user.id) {
+            Some((last_message_at, _)) => *last_message_at,
+            None => return Ok(()),
+        };
+
+        let elapsed = last_message_at.elapsed();
+        if elapsed < cooldown {
+            let retry_after = (cooldown - elapsed).as_millis() as u64;
+            return Err(ResponseError::TooManyRequests { retry_after });
+        }
+
+        Ok(())
+    }
+
+    fn create_message(&mut self, id: &SessionId, content: String) -> Result<(), ResponseError> {
+        let user = match self.members.get(id).and_then(|m| m.user.clone()) {
+            Some(user) => user,
+            None => {
+                return Err(ResponseError::AccessError(
+                    "Anonymous users can not send messages",
+                ))
+            }
+        };
+
+        if !asserts::valid_message(&content) {
+            return Err(ResponseError::BadRequestMessage(
+                "Message should be 1-500 characters long",
+            ));
+        }
+
+        let cooldown = self.message_cooldown(&user)?;
+        self.assert_cooldown(&user, cooldown)?;
+
+        let channel_id = self
+            .channel_id
+            .clone()
+            .ok_or(ResponseError::InternalError)?;
+
+        let conn = self.conn()?;
+        let message = db::NewMessage {
+            channel_id,
+            user_id: user.id.clone(),
+            content: content.trim(),
+        }
+        .create(&conn)?;
+
+        self.last_messages.retain(|_, (last_message_at, cooldown)| {
+            last_message_at.elapsed() < LAST_MESSAGE_TTL.max(*cooldown)
+        });
+        self.last_messages
+            .insert(user.id.clone(), (Instant::now(), cooldown));
+
+        self.broadcast_to_readers(&ServerEvent::Message(ChatMessage {
+            message,
+            user: db::PublicUser::from(&user),
+        }));
+        Ok(())
+    }
+
+    /// Resolve videos and add them to playlist.
+    ///
+    /// Resolving takes a while, so it's done in background and reply is sent once it's done.
+    fn add_videos(
+        &mut self,
+        id: SessionId,
+        request_id: Option<u64>,
+        videos: Vec<playlist::AddVideo>,
+        ctx: &mut Context<Self>,
+    ) {
+        let allowed = self.conn().and_then(|conn| {
+            playlist::assert_can_add(self.member_user(&id), &self.room, &videos, &conn)
+        });
+        if let Err(err) = allowed {
+            return self.reply(&id, request_id, Err(err));
+        }
+
+        let resolver = self.resolver.clone();
+        let resolving = async move { playlist::resolve(&resolver, videos).await };
+
+        ctx.spawn(resolving.into_actor(self).map(move |resolved, act, ctx| {
+            let result = resolved.and_then(|videos| {
+                let conn = act.conn()?;
+                let _ = playlist::add(act.member_user(&id), &act.room, videos, &conn)?;
+                act.reload_playlist(ctx)
+            });
+
+            act.reply(&id, request_id, result);
+        }));
+    }
+
+    fn handle_event(
+        &mut self,
+        id: &SessionId,
+        event: ClientEvent,
+        ctx: &mut Context<Self>,
+    ) -> Result<(), ResponseError> {
+        match event {
+            ClientEvent::Hello { .. } => {
+                return Err(ResponseError::BadRequestMessage("Already joined"));
+            }
+            ClientEvent::PlayerPause => {
+                self.assert_leader(id)?;
+                self.assert_allowed(id, ActionType::PlayerPause, "Not allowed to pause")?;
+                self.assert_playing()?;
+                self.player.pause();
+                self.player_updated(ctx);
+            }
+            ClientEvent::PlayerResume => {
+                self.assert_leader(id)?;
+                self.assert_allowed(id, ActionType::PlayerResume, "Not allowed to resume")?;
+                self.assert_playing()?;
+                self.player.resume();
+                self.player_updated(ctx);
+            }
+            ClientEvent::PlayerRewind { position } => {
+                self.assert_leader(id)?;
+                self.assert_allowed(id, ActionType::PlayerRewind, "Not allowed to rewind")?;
+                self.assert_playing()?;
+                self.player.rewind(Duration::from_millis(position));
+                self.player_updated(ctx);
+            }
+            ClientEvent::PlayerSkip => {
+                self.assert_leader(id)?;
+                self.assert_allowed(id, ActionType::VideoDelete, "Not allowed to skip videos")?;
+                self.assert_playing()?;
+                self.advance(Advance::Skipped, ctx)?;
+            }
+            ClientEvent::PlayerVoteSkip => {
+                self.assert_allowed(id, ActionType::VideoWatch, "Not allowed to vote")?;
+                self.assert_playing()?;
+
+                let voter = match self.members.get(id) {
+                    Some(member) => member.voter(*id),
+                    None => return Ok(()),
+                };
+                if !self.skip_votes.insert(voter) {
+                    return Err(ResponseError::BadRequestMessage("Already voted"));
+                }
+
+                self.count_skip_votes(ctx)?;
+            }
+            ClientEvent::Message { content } => {
+                self.assert_allowed(
+                    id,
+                    ActionType::MessageCreate,
+                    "Not allowed to send messages",
+                )?;
+                self.create_message(id, content)?;
+            }
+            ClientEvent::LeaderTransfer { user_id } => {
+                self.transfer_leadership(id, &user_id)?;
+            }
+            ClientEvent::TimePing { .. } => unreachable!("Time is synced by sessions"),
+            ClientEvent::PlaylistAdd { .. } => unreachable!("Videos are added asynchronously"),
+            ClientEvent::PlaylistRemove { video_id } => {
+                let conn = self.conn()?;
+                let _ = playlist::remove(self.member_user(id), &self.room, video_id, &conn)?;
+                self.reload_playlist(ctx)?;
+            }
+            ClientEvent::PlaylistMove { video_id, to } => {
+                let conn = self.conn()?;
+                let playing_id = self.player.video().map(|video| video.id.as_str());
+                let user = self.member_user(id);
+                let _ = playlist::move_video(user, &self.room, video_id, to, playing_id, &conn)?;
+                self.reload_playlist(ctx)?;
+            }
+            ClientEvent::PlaylistClear => {
+                let conn = self.conn()?;
+                let _ = playlist::clear(self.member_user(id), &self.room, &conn)?;
+                self.reload_playlist(ctx)?;
+            }
+            ClientEvent::PlaylistShuffle => {
+                let conn = self.conn()?;
+                let playing_id = self.player.video().map(|v| v.id.as_str());
+                let _ = playlist::shuffle(self.member_user(id), &self.room, playing_id, &conn)?;
+                self.reload_playlist(ctx)?;
+            }
+            ClientEvent::SubtitlesOffset {
+                subtitles_id,
+                time_offset,
+            } => {
+                let conn = self.conn()?;
+                let user = self.member_user(id);
+                let track =
+                    subtitles::set_offset(user, &self.room, subtitles_id, time_offset, &conn)?;
+                self.subtitles_updated(&track.video_id);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Actor for RoomHub {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Room hub {:?} started", self.room.path);
+
+        let conn = match self.conn() {
+            Ok(conn) => conn,
+            Err(_) => return,
+        };
+
+        self.playlist = playlist::list(&self.room, &conn).unwrap_or_default();
+        self.player = self.restore_player(&conn);
+        self.schedule_advance(ctx);
+        self.schedule_live_check(ctx);
+        self.schedule_idle_stop(ctx);
+
+        ctx.run_interval(CHECKPOINT_INTERVAL, |act, _| {
+            if let Err(err) = act.save_checkpoint() {
+                error!(
+                    "Couldn't save playback of room {:?}: {:?}",
+                    act.room.path, err
+                );
+            }
+        });
+
+        self.channel_id = db::RoomChannel::get_or_create(self.room.id.clone(), &conn)
+            .map(|room_channel| room_channel.channel_id)
+            .ok();
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        if let Err(err) = self.save_checkpoint() {
+            error!(
+                "Couldn't save playback of room {:?}: {:?}",
+                self.room.path, err
+            );
+        }
+
+        info!("Room hub {:?} stopped", self.room.path);
+    }
+}
+
+impl Handler<Connect> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: Connect, ctx: &mut Self::Context) {
+        let can_read_messages = self
+            .is_allowed(msg.user.as_ref(), ActionType::MessageRead)
+            .unwrap_or(false);
+        let can_read_history = can_read_messages
+            && self
+                .is_allowed(msg.user.as_ref(), ActionType::MessageHistory)
+                .unwrap_or(false);
+
+        let can_watch = self
+            .is_allowed(msg.user.as_ref(), ActionType::VideoWatch)
+            .unwrap_or(false);
+
+        let member = Member {
+            user: msg.user,
+            addr: msg.addr,
+            can_read_messages,
+            can_watch,
+            joined_at: Instant::now(),
+        };
+        let info = member.info(msg.id);
+
+        self.members.insert(msg.id, member);
+        self.schedule_idle_stop(ctx);
+
+        let welcome = ServerEvent::Welcome {
+            version: PROTOCOL_VERSION,
+            session_id: msg.id,
+        };
+        self.send(&msg.id, &welcome);
+        self.send(&msg.id, &ServerEvent::Members(self.members_info()));
+        self.send(&msg.id, &ServerEvent::Playlist(self.playlist.clone()));
+        self.send(&msg.id, &ServerEvent::Player(self.player.state()));
+        if let Some(event) = self.subtitles_event() {
+            self.send(&msg.id, &event);
+        }
+        if let Some(event) = self.emotes_event() {
+            self.send(&msg.id, &event);
+        }
+        if self.room.is_leader_mode {
+            self.send(&msg.id, &self.leader_event());
+        }
+
+        if can_read_history {
+            if let Err(err) = self.send_message_history(&msg.id) {
+                error!("Couldn't send message history: {}", err);
+            }
+        }
+
+        self.broadcast(&ServerEvent::MemberJoined(info), Some(&msg.id));
+        self.sync_leader();
+
+        // Newcomer raises the bar.
+        if !self.skip_votes.is_empty() {
+            if let Err(err) = self.count_skip_votes(ctx) {
+                error!("Couldn't count skip votes: {}", err);
+            }
+        }
+    }
+}
+
+impl Handler<Disconnect> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, ctx: &mut Self::Context) {
+        if let Some(member) = self.members.remove(&msg.id) {
+            self.broadcast(&ServerEvent::MemberLeft(member.info(msg.id)), None);
+        }
+        self.sync_leader();
+        self.schedule_idle_stop(ctx);
+
+        // Fewer viewers might be enough to skip.
+        if !self.skip_votes.is_empty() {
+            if let Err(err) = self.count_skip_votes(ctx) {
+                error!("Couldn't count skip votes: {}", err);
+            }
+        }
+    }
+}
+
+impl Handler<ClientMessage> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: ClientMessage, ctx: &mut Self::Context) {
+        match msg.event {
+            ClientEvent::PlaylistAdd { videos } => {
+                self.add_videos(msg.id, msg.request_id, videos, ctx)
+            }
+            event => {
+                let result = self.handle_event(&msg.id, event, ctx);
+                self.reply(&msg.id, msg.request_id, result);
+            }
+        }
+    }
+}
+
+impl Handler<PlaylistUpdated> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, _: PlaylistUpdated, ctx: &mut Self::Context) {
+        if let Err(err) = self.reload_playlist(ctx) {
+            error!(
+                "Couldn't reload playlist of room {:?}: {}",
+                self.room.path, err
+            );
+        }
+    }
+}
+
+impl Handler<SubtitlesUpdated> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubtitlesUpdated, _: &mut Self::Context) {
+        self.subtitles_updated(&msg.video_id);
+    }
+}
+
+impl Handler<EmotesUpdated> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, _: EmotesUpdated, _: &mut Self::Context) {
+        if let Some(event) = self.emotes_event() {
+            self.broadcast(&event, None);
+        }
+    }
+}
+
+impl Handler<RoomUpdated> for RoomHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: RoomUpdated, ctx: &mut Self::Context) {
+        self.room = msg.0;
+        self.sync_leader();
+
+        if !self.skip_votes.is_empty() {
+            if let Err(err) = self.count_skip_votes(ctx) {
+                error!("Couldn't count skip votes: {}", err);
+            }
+        }
+    }
+}
+
+impl Handler<GetPresence> for RoomHub {
+    type Result = MessageResult<GetPresence>;
+
+    fn handle(&mut self, _: GetPresence, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.presence())
+    }
+}
+
+/// Running room hubs, keyed by `Room.id`.
+#[derive(Clone, Default)]
+pub struct Hubs(Arc<Mutex<HashMap<String, Addr<RoomHub>>>>);
+
+impl Hubs {
+    /// Get hub of the room, starting a new one if there is none yet.
+    pub fn get_or_start(
+        &self,
+        room: &db::Room,
+        pool: &db::DbPool,
+        resolver: &Resolver,
+    ) -> Addr<RoomHub> {
+        let mut hubs = self.0.lock().unwrap();
+
+        match hubs.get(&room.id) {
+            Some(hub) if hub.connected() => hub.clone(),
+            _ => {
+                let hub = RoomHub::new(room.clone(), pool.clone(), resolver.clone(), self.clone())
+                    .start();
+                hubs.insert(room.id.clone(), hub.clone());
+                hub
+            }
+        }
+    }
+
+    /// Forget hub of the room, unless it was replaced by another one already.
+    fn remove(&self, room_id: &str, hub: &Addr<RoomHub>) {
+        let mut hubs = self.0.lock().unwrap();
+
+        if hubs.get(room_id) == Some(hub) {
+            hubs.remove(room_id);
+        }
+    }
+
+    /// Get hub of the room, if it's running.
+    fn get(&self, room_id: &str) -> Option<Addr<RoomHub>> {
+        match self.0.lock().unwrap().get(room_id) {
+            Some(hub) if hub.connected() => Some(hub.clone()),
+            _ => None,
+        }
+    }
+
+    /// Presence of the room. Rooms without running hub are empty.
+    pub async fn presence(&self, room_id: &str) -> Presence {
+        let hub = match self.get(room_id) {
+            Some(hub) => hub,
+            None => return Presence::default(),
+        };
+
+        hub.send(GetPresence).await.unwrap_or_else(|err| {
+            error!("Couldn't get presence of room {:?}: {}", room_id, err);
+            Presence::default()
+        })
+    }
+
+    /// Let hub of the room know, that room settings were changed.
+    pub fn room_updated(&self, room: &db::Room) {
+        if let Some(hub) = self.get(&room.id) {
+            hub.do_send(RoomUpdated(room.clone()));
+        }
+    }
+
+    /// Let hub of the room know, that playlist was changed.
+    pub fn playlist_updated(&self, room_id: &str) {
+        if let Some(hub) = self.get(room_id) {
+            hub.do_send(PlaylistUpdated);
+        }
+    }
+
+    /// Let hub of the room know, that subtitle tracks of the video were changed.
+    pub fn subtitles_updated(&self, room_id: &str, video_id: &str) {
+        if let Some(hub) = self.get(room_id) {
+            hub.do_send(SubtitlesUpdated {
+                video_id: video_id.to_owned(),
+            });
+        }
+    }
+
+    /// Let hub of the room know, that emotes were changed.
+    pub fn emotes_updated(&self, room_id: &str) {
+        if let Some(hub) = self.get(room_id) {
+            hub.do_send(EmotesUpdated);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use super::asserts;
+use super::States;
+use crate::db;
+use crate::server::errors::ResponseError;
+use actix_web::web::{Path, Payload};
+use actix_web::HttpRequest;
+use actix_web_actors::ws;
+use serde::Deserialize;
+
+mod clock;
+mod events;
+mod hub;
+mod player;
+mod session;
+
+pub use hub::Hubs;
+use session::WsSession;
+
+#[derive(Deserialize, Debug)]
+pub struct Info {
+    room_path: String,
+}
+
+pub async fn index(
+    req: HttpRequest,
+    states: States,
+    stream: Payload,
+    info: Path<Info>,
+    user: Option<db::User>,
+) -> super::RouteResult {
+    // Origin is not checked in debug builds, so the app can be served from elsewhere.
+    if !cfg!(debug_assertions) && !asserts::valid_origin(&req) {
+        return Err(ResponseError::AccessError("Bad origin"));
+    }
+
+    let conn = states.pool.get().unwrap();
+    let room_path = info.room_path.clone();
+    let room = db::Room::by_path(room_path, &conn)?;
+
+    let hub = states
+        .hubs
+        .get_or_start(&room, &states.pool, &states.resolver);
+
+    ws::start(WsSession::new(user, hub), &req, stream).map_err(From::from)
+}
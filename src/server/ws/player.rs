@@ -0,0 +1,143 @@
+use super::clock;
+use crate::db;
+use chrono::Utc;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Snapshot of player state, as seen by clients.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerState {
+    pub video: Option<db::Video>,
+    /// Position in milliseconds at the moment of `server_time`.
+    pub position: u64,
+    pub is_paused: bool,
+    /// Unix timestamp in milliseconds.
+    pub updated_at: i64,
+    /// Server clock reading, when `position` was measured.
+    ///
+    /// Client should seek to `position + (now - server_time)` of server clock,
+    /// unless player is paused.
+    pub server_time: f64,
+}
+
+/// Server-authoritative player.
+///
+/// Player doesn't tick. Instead it remembers position at the time of the last update,
+/// so current position can be computed at any moment.
+pub struct Player {
+    video: Option<db::Video>,
+    /// Position at the moment of `updated_at`.
+    position: Duration,
+    is_paused: bool,
+    updated_at: Instant,
+}
+
+impl Player {
+    pub fn new(video: Option<db::Video>) -> Player {
+        Player {
+            video,
+            position: Duration::from_secs(0),
+            is_paused: false,
+            updated_at: Instant::now(),
+        }
+    }
+
+    /// Continue playback from a checkpoint.
+    pub fn restore(video: db::Video, position: Duration, is_paused: bool) -> Player {
+        let mut player = Player::new(Some(video));
+        player.position = player.clamp(position);
+        player.is_paused = is_paused;
+        player
+    }
+
+    pub fn video(&self) -> Option<&db::Video> {
+        self.video.as_ref()
+    }
+
+    /// Start playing another video from the beginning.
+    pub fn set_video(&mut self, video: Option<db::Video>) {
+        self.video = video;
+        self.position = Duration::from_secs(0);
+        self.is_paused = false;
+        self.updated_at = Instant::now();
+    }
+
+    /// Duration of current video, if known.
+    fn duration(&self) -> Option<Duration> {
+        self.video
+            .as_ref()
+            .and_then(|v| v.duration)
+            .filter(|d| *d > 0)
+            .map(|d| Duration::from_secs(d as u64))
+    }
+
+    fn clamp(&self, position: Duration) -> Duration {
+        match self.duration() {
+            Some(duration) if position > duration => duration,
+            _ => position,
+        }
+    }
+
+    /// Current position, computed from the last update.
+    pub fn position(&self) -> Duration {
+        self.position_at(Instant::now())
+    }
+
+    fn position_at(&self, instant: Instant) -> Duration {
+        if self.is_paused {
+            return self.position;
+        }
+
+        let elapsed = instant
+            .checked_duration_since(self.updated_at)
+            .unwrap_or_default();
+        self.clamp(self.position + elapsed)
+    }
+
+    /// Time left until current video ends.
+    ///
+    /// `None` if player is paused, or video is a live stream or of unknown duration.
+    pub fn remaining(&self) -> Option<Duration> {
+        let video = self.video.as_ref()?;
+        if self.is_paused || video.is_live {
+            return None;
+        }
+
+        let duration = self.duration()?;
+        Some(duration.checked_sub(self.position()).unwrap_or_default())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn pause(&mut self) {
+        self.position = self.position();
+        self.is_paused = true;
+        self.updated_at = Instant::now();
+    }
+
+    pub fn resume(&mut self) {
+        self.position = self.position();
+        self.is_paused = false;
+        self.updated_at = Instant::now();
+    }
+
+    pub fn rewind(&mut self, position: Duration) {
+        self.position = self.clamp(position);
+        self.updated_at = Instant::now();
+    }
+
+    pub fn state(&self) -> PlayerState {
+        let now = Instant::now();
+
+        PlayerState {
+            video: self.video.clone(),
+            position: self.position_at(now).as_millis() as u64,
+            is_paused: self.is_paused,
+            updated_at: Utc::now().timestamp_millis(),
+            server_time: clock::at(now),
+        }
+    }
+}
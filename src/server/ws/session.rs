@@ -0,0 +1,185 @@
+use super::clock;
+use super::events::{ClientEvent, ClientFrame, ErrorFrame, ServerEvent, PROTOCOL_VERSION};
+use super::hub::{self, RoomHub, SessionId};
+use crate::db;
+use crate::server::errors::ResponseError;
+use actix::prelude::*;
+use actix_web_actors::ws;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How often heartbeat pings are sent
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long before lack of client response causes a timeout
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Websocket connection of a single client.
+pub struct WsSession {
+    id: SessionId,
+    /// `None` if user is anonymous
+    user: Option<db::User>,
+    hub: Addr<RoomHub>,
+    /// Whether handshake is done and session is registered in the hub
+    joined: bool,
+    /// Last time client responded to ping
+    hb: Instant,
+}
+
+impl WsSession {
+    pub fn new(user: Option<db::User>, hub: Addr<RoomHub>) -> WsSession {
+        WsSession {
+            id: Uuid::new_v4(),
+            user,
+            hub,
+            joined: false,
+            hb: Instant::now(),
+        }
+    }
+
+    /// Ping client every `HEARTBEAT_INTERVAL` and drop connection
+    /// if there were no response within `CLIENT_TIMEOUT`.
+    ///
+    /// Connection is dropped as well, if hub has stopped meanwhile,
+    /// so client reconnects and gets a running one.
+    fn heartbeat(&self, ctx: &mut <Self as Actor>::Context) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                debug!("Websocket client {} timed out", act.id);
+                ctx.stop();
+                return;
+            }
+
+            if !act.hub.connected() {
+                debug!("Hub of websocket client {} has stopped", act.id);
+                ctx.close(Some(ws::CloseCode::Restart.into()));
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+    }
+
+    fn send_error(
+        &self,
+        request_id: Option<u64>,
+        err: &ResponseError,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        ctx.text(ServerEvent::error(request_id, err).to_json());
+    }
+
+    /// Join the hub, if client speaks our version of protocol.
+    fn handshake(
+        &mut self,
+        version: u32,
+        request_id: Option<u64>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        if self.joined {
+            let err = ResponseError::BadRequestMessage("Already joined");
+            return self.send_error(request_id, &err, ctx);
+        }
+
+        if version != PROTOCOL_VERSION {
+            let err = ResponseError::BadRequestMessage("Unsupported protocol version");
+            self.send_error(request_id, &err, ctx);
+            ctx.close(Some(ws::CloseCode::Unsupported.into()));
+            ctx.stop();
+            return;
+        }
+
+        self.joined = true;
+        self.hub.do_send(hub::Connect {
+            id: self.id,
+            user: self.user.clone(),
+            addr: ctx.address().recipient(),
+        });
+    }
+
+    fn handle_text(&mut self, text: &str, ctx: &mut <Self as Actor>::Context) {
+        let received_at = clock::now();
+
+        let frame = match serde_json::from_str::<ClientFrame>(text) {
+            Ok(frame) => frame,
+            Err(err) => {
+                let mut error = ErrorFrame::new(None, &ResponseError::BadRequest);
+                error.message = format!("Malformed frame: {}", err);
+                ctx.text(ServerEvent::Error(error).to_json());
+                return;
+            }
+        };
+
+        match frame.event {
+            ClientEvent::Hello { version } => self.handshake(version, frame.request_id, ctx),
+            // Answered right away, as hub's mailbox would add latency.
+            ClientEvent::TimePing { client_time } => {
+                let pong = ServerEvent::TimePong {
+                    client_time,
+                    received_at,
+                    sent_at: clock::now(),
+                };
+                ctx.text(pong.to_json());
+            }
+            event if self.joined => self.hub.do_send(hub::ClientMessage {
+                id: self.id,
+                request_id: frame.request_id,
+                event,
+            }),
+            _ => {
+                let err = ResponseError::BadRequestMessage("Handshake is required");
+                self.send_error(frame.request_id, &err, ctx);
+            }
+        }
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.heartbeat(ctx);
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        if self.joined {
+            self.hub.do_send(hub::Disconnect { id: self.id });
+        }
+        Running::Stop
+    }
+}
+
+impl Handler<hub::Event> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: hub::Event, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                debug!("Websocket protocol error: {:?}", err);
+                ctx.stop();
+                return;
+            }
+        };
+
+        match msg {
+            ws::Message::Ping(msg) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            ws::Message::Pong(_) => self.hb = Instant::now(),
+            ws::Message::Text(text) => self.handle_text(&text, ctx),
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => (),
+        }
+    }
+}
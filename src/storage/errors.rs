@@ -0,0 +1,27 @@
+use crate::db::DieselError;
+use failure::Fail;
+
+#[derive(Fail, Debug)]
+pub enum StorageError {
+    #[fail(display = "File not found")]
+    NotFound,
+    #[fail(display = "Storage failure: {}", _0)]
+    Io(String),
+    #[fail(display = "Database failure: {}", _0)]
+    Database(DieselError),
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> StorageError {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => StorageError::NotFound,
+            _ => StorageError::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<DieselError> for StorageError {
+    fn from(err: DieselError) -> StorageError {
+        StorageError::Database(err)
+    }
+}
@@ -0,0 +1,125 @@
+// Garbage collection of files, which are referenced by nothing.
+//
+// Files are recorded before anything points at them, e.g. subtitles are stored before
+// their track is created, so only files saved longer than `GRACE_PERIOD` ago are collected.
+// Stored object is removed together with the last file of its content.
+//
+// Content is locked while it's collected, as well as while it's saved, so it's never
+// deleted from storage right after it was saved again.
+
+use super::{key, Files, StorageError};
+use crate::db;
+use crate::diesel::prelude::PgConnection;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How long unreferenced files are kept.
+const GRACE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often files are collected.
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What was removed, or would be removed in dry run.
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub files: Vec<db::File>,
+    /// Keys of stored objects.
+    pub objects: Vec<String>,
+}
+
+impl Files {
+    /// Remove unreferenced files. Nothing is removed in `dry_run`, only reported.
+    pub async fn collect_garbage(
+        &self,
+        dry_run: bool,
+        conn: &PgConnection,
+    ) -> Result<GcReport, StorageError> {
+        let grace_period = chrono::Duration::from_std(GRACE_PERIOD).unwrap_or_default();
+        let saved_before = Utc::now().naive_utc() - grace_period;
+        let orphans = db::File::list_orphaned(saved_before, None, conn)?;
+
+        let mut by_content: HashMap<(String, String), Vec<db::File>> = HashMap::new();
+        for file in orphans {
+            by_content
+                .entry((file.hash.clone(), file.ext.clone()))
+                .or_default()
+                .push(file);
+        }
+
+        let mut report = GcReport {
+            dry_run,
+            ..GcReport::default()
+        };
+
+        for ((hash, ext), files) in by_content {
+            let key = key(&hash, &ext);
+
+            if dry_run {
+                // Object is removed, unless some file of the same content is still in use.
+                if db::File::count_by_hash(&hash, &ext, conn)? == files.len() as i64 {
+                    report.objects.push(key);
+                }
+                report.files.extend(files);
+                continue;
+            }
+
+            let _lock = db::File::lock_content(&hash, conn)?;
+
+            // List again, as the same content might have been saved since.
+            let files = db::File::list_orphaned(saved_before, Some((&hash, &ext)), conn)?;
+            for file in &files {
+                file.delete(conn)?;
+            }
+            report.files.extend(files);
+
+            if db::File::count_by_hash(&hash, &ext, conn)? == 0 {
+                self.storage.delete(&key).await.map_err(|err| {
+                    error!("Couldn't delete stored file {:?}: {}", key, err);
+                    err
+                })?;
+                report.objects.push(key);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Collect garbage every `GC_INTERVAL` in background.
+    pub fn spawn_collector(&self, pool: db::DbPool, dry_run: bool) {
+        let files = self.clone();
+
+        actix_rt::spawn(async move {
+            let mut interval = tokio::time::interval(GC_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                let conn = match pool.get() {
+                    Ok(conn) => conn,
+                    Err(err) => {
+                        error!("Couldn't get db connection: {}", err);
+                        continue;
+                    }
+                };
+
+                match files.collect_garbage(dry_run, &conn).await {
+                    Ok(report) if dry_run => info!(
+                        "Files to collect: {} files, {} stored objects: {:?}",
+                        report.files.len(),
+                        report.objects.len(),
+                        report.objects
+                    ),
+                    Ok(report) => info!(
+                        "Collected {} files, {} stored objects",
+                        report.files.len(),
+                        report.objects.len()
+                    ),
+                    Err(err) => error!("Couldn't collect files: {}", err),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,69 @@
+use super::{Storage, StorageError};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Objects stored as files in a local directory. Keys are paths relative to `root`.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new<P: Into<PathBuf>>(root: P) -> LocalStorage {
+        LocalStorage { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+impl Storage for LocalStorage {
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, StorageError>> {
+        async move { Ok(tokio::fs::metadata(self.path(key)).await.is_ok()) }.boxed()
+    }
+
+    /// Write is atomic, so there are no partially written files.
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: &'a [u8],
+        _content_type: &'a str,
+    ) -> BoxFuture<'a, Result<(), StorageError>> {
+        async move {
+            let path = self.path(key);
+            let dir = path.parent().unwrap_or(&self.root);
+            tokio::fs::create_dir_all(dir).await?;
+
+            let temp = dir.join(format!(".{}.tmp", Uuid::new_v4()));
+            if let Err(err) = tokio::fs::write(&temp, data).await {
+                let _ = tokio::fs::remove_file(&temp).await;
+                return Err(err.into());
+            }
+            tokio::fs::rename(&temp, &path).await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, StorageError>> {
+        async move {
+            let data = tokio::fs::read(self.path(key)).await?;
+            Ok(data)
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), StorageError>> {
+        async move {
+            match tokio::fs::remove_file(self.path(key)).await {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err.into()),
+            }
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,205 @@
+// Content-addressed file storage.
+//
+// Files are named by SHA-256 hash of their content, so the same content is stored once,
+// no matter how many times it's uploaded, and stored files never change.
+// Uploads of the same content share a record in `files`, as well as the stored file.
+//
+// Where files are kept is up to `Storage` backend, chosen by `STORAGE_BACKEND`.
+
+mod errors;
+mod gc;
+mod local;
+mod s3;
+
+pub use errors::*;
+pub use gc::GcReport;
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+use crate::db;
+use crate::diesel::prelude::PgConnection;
+use crate::env;
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Hex encoded SHA-256 of the content.
+pub fn hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Content type of files with extension `ext`.
+pub fn content_type(ext: &str) -> &'static str {
+    match ext.to_lowercase().as_str() {
+        "vtt" => "text/vtt; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Whether files with extension `ext` can be shown by browsers.
+///
+/// Files are served from the app origin, so types, which can run scripts, like SVG,
+/// are only downloaded.
+pub fn is_inline(ext: &str) -> bool {
+    matches!(
+        ext.to_lowercase().as_str(),
+        "vtt" | "png" | "jpg" | "jpeg" | "gif" | "webp" | "mp4" | "webm" | "json" | "txt"
+    )
+}
+
+/// Key of the file in storage.
+///
+/// Files are spread over prefixes by the first two characters of their hash,
+/// so no directory grows too big.
+fn key(hash: &str, ext: &str) -> String {
+    let prefix = hash.get(..2).unwrap_or(hash);
+    format!("{}/{}.{}", prefix, hash, ext)
+}
+
+/// Place where objects are kept, addressed by keys like `ab/abcdef.ext`.
+pub trait Storage: Send + Sync {
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, StorageError>>;
+
+    /// Write object, replacing existing one.
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: &'a [u8],
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<(), StorageError>>;
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, StorageError>>;
+
+    /// Delete object. Deleting missing object is not an error.
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), StorageError>>;
+}
+
+/// Stored files, recorded in `files`.
+#[derive(Clone)]
+pub struct Files {
+    storage: Arc<dyn Storage>,
+}
+
+impl Files {
+    pub fn new(storage: Arc<dyn Storage>) -> Files {
+        Files { storage }
+    }
+
+    /// Store file and record it in `files`.
+    ///
+    /// If the same content was already stored, the existing record is returned,
+    /// marked as saved now, so it's not taken for garbage. See `gc` module.
+    pub async fn save(
+        &self,
+        data: &[u8],
+        ext: &str,
+        conn: &PgConnection,
+    ) -> Result<db::File, StorageError> {
+        let hash = hash(data);
+        let key = key(&hash, ext);
+
+        // Garbage collector waits until the content is both recorded and stored.
+        let _lock = db::File::lock_content(&hash, conn)?;
+
+        let file = match db::File::by_hash(&hash, ext, conn)? {
+            Some(file) => file.mark_saved(conn)?,
+            None => db::NewFile { hash: &hash, ext }.create(conn)?,
+        };
+
+        if !self.storage.exists(&key).await? {
+            self.storage
+                .put(&key, data, content_type(ext))
+                .await
+                .map_err(|err| {
+                    error!("Couldn't store file {:?}: {}", key, err);
+                    err
+                })?;
+        }
+
+        Ok(file)
+    }
+
+    pub async fn read(&self, file: &db::File) -> Result<Vec<u8>, StorageError> {
+        let key = key(&file.hash, &file.ext);
+
+        self.storage.get(&key).await.map_err(|err| {
+            error!("Couldn't read file {:?}: {}", key, err);
+            err
+        })
+    }
+}
+
+impl Default for Files {
+    /// Backend is configured by environment variables.
+    fn default() -> Files {
+        let storage: Arc<dyn Storage> = match env::STORAGE_BACKEND.as_str() {
+            "local" => Arc::new(LocalStorage::new(env::UPLOADS_DIR.as_str())),
+            "s3" => {
+                let storage = S3Storage::new(
+                    &env::S3_ENDPOINT,
+                    &env::S3_REGION,
+                    &env::S3_BUCKET,
+                    &env::S3_ACCESS_KEY,
+                    &env::S3_SECRET_KEY,
+                );
+                Arc::new(storage.expect("S3 storage"))
+            }
+            backend => panic!("Unknown storage backend {:?}", backend),
+        };
+
+        Files::new(storage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_content() {
+        assert_eq!(
+            hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(hash(b"abc"), hash(b"abd"));
+    }
+
+    #[test]
+    fn derives_keys_from_hash() {
+        let hash = hash(b"abc");
+        assert_eq!(
+            key(&hash, "png"),
+            "ba/ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad.png"
+        );
+        assert_eq!(key("a", "vtt"), "a/a.vtt");
+    }
+
+    #[test]
+    fn content_type_by_extension() {
+        assert_eq!(content_type("PNG"), "image/png");
+        assert_eq!(content_type("vtt"), "text/vtt; charset=utf-8");
+        assert_eq!(content_type("exe"), "application/octet-stream");
+    }
+
+    #[test]
+    fn scriptable_files_are_not_inline() {
+        assert!(is_inline("PNG"));
+        assert!(is_inline("vtt"));
+        assert!(!is_inline("svg"));
+        assert!(!is_inline("html"));
+        assert!(!is_inline("exe"));
+    }
+}
@@ -0,0 +1,289 @@
+// In-process HTTP server, standing in for remote services in tests.
+//
+// Every connection serves a single request and is closed, which is all reqwest needs.
+
+use crate::storage::{Storage, StorageError};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests bigger than that are not read.
+const MAX_REQUEST_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Path with query, as requested.
+    pub target: String,
+    /// Keyed by lowercase name.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    pub fn query(&self) -> &str {
+        self.target.splitn(2, '?').nth(1).unwrap_or_default()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Response {
+        self.body = body.into();
+        self
+    }
+
+    /// Serve `data` the way file servers do, honoring `Range` header.
+    pub fn file(req: &Request, content_type: &str, data: &[u8]) -> Response {
+        let range = req
+            .header("range")
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| {
+                let mut bounds = range.splitn(2, '-');
+                let start = bounds.next()?.parse::<usize>().ok()?;
+                let end = bounds.next()?.parse::<usize>().ok();
+                Some((start, end))
+            });
+
+        let (start, end) = match range {
+            Some(range) => range,
+            None => {
+                return Response::new(200)
+                    .header("Content-Type", content_type)
+                    .body(data)
+            }
+        };
+
+        if start >= data.len() {
+            return Response::new(416).header("Content-Range", &format!("bytes */{}", data.len()));
+        }
+
+        let end = end.unwrap_or(usize::max_value()).min(data.len() - 1);
+        Response::new(206)
+            .header("Content-Type", content_type)
+            .header(
+                "Content-Range",
+                &format!("bytes {}-{}/{}", start, end, data.len()),
+            )
+            .body(&data[start..=end])
+    }
+}
+
+type Handler = Arc<dyn Fn(&Request) -> Response + Send + Sync>;
+
+pub struct StubServer {
+    addr: SocketAddr,
+}
+
+impl StubServer {
+    /// Start serving requests on a random local port, until runtime of the test is shut down.
+    pub async fn start<F>(handler: F) -> StubServer
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler: Handler = Arc::new(handler);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, handler.clone()));
+            }
+        });
+
+        StubServer { addr }
+    }
+
+    pub fn url(&self, target: &str) -> String {
+        format!("http://{}{}", self.addr, target)
+    }
+}
+
+/// Client, which reaches stub servers directly, whatever proxy is configured.
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder().no_proxy().build().unwrap()
+}
+
+async fn serve(mut stream: TcpStream, handler: Handler) {
+    let req = match read_request(&mut stream).await {
+        Some(req) => req,
+        None => return,
+    };
+    let res = handler(&req);
+
+    let mut head = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n",
+        res.status,
+        res.body.len()
+    );
+    for (name, value) in &res.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let _ = stream.write_all(head.as_bytes()).await;
+    if req.method != "HEAD" {
+        let _ = stream.write_all(&res.body).await;
+    }
+    let _ = stream.shutdown(std::net::Shutdown::Write);
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 8192];
+
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        let len = stream.read(&mut chunk).await.ok()?;
+        if len == 0 || buf.len() > MAX_REQUEST_SIZE {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..len]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_owned();
+    let target = request_line.next()?.to_owned();
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| {
+            let colon = line.find(':')?;
+            let name = line[..colon].trim().to_lowercase();
+            Some((name, line[colon + 1..].trim().to_owned()))
+        })
+        .collect();
+
+    let content_length = headers
+        .get("content-length")
+        .and_then(|len| len.parse::<usize>().ok())
+        .unwrap_or(0)
+        .min(MAX_REQUEST_SIZE);
+    let mut body = buf[head_end + 4..].to_vec();
+    while body.len() < content_length {
+        let len = stream.read(&mut chunk).await.ok()?;
+        if len == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..len]);
+    }
+
+    Some(Request {
+        method,
+        target,
+        headers,
+        body,
+    })
+}
+
+/// MP4 box of `kind` with 32-bit size.
+pub fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut data = (8 + body.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    data
+}
+
+/// MP4 file with a single `avc1` video track, which has `moov` after `mdat_len` bytes of media.
+pub fn mp4_file(duration_ms: u32, width: u16, height: u16, mdat_len: usize) -> Vec<u8> {
+    let mut mvhd = vec![0; 100];
+    mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+    mvhd[16..20].copy_from_slice(&duration_ms.to_be_bytes());
+
+    let mut tkhd = vec![0; 84];
+    tkhd[76..78].copy_from_slice(&width.to_be_bytes());
+    tkhd[80..82].copy_from_slice(&height.to_be_bytes());
+
+    let mut hdlr = vec![0; 25];
+    hdlr[8..12].copy_from_slice(b"vide");
+
+    let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    stsd.extend(mp4_box(b"avc1", &[0; 78]));
+
+    let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+    let minf = mp4_box(b"minf", &stbl);
+    let mdia = mp4_box(b"mdia", &[mp4_box(b"hdlr", &hdlr), minf].concat());
+    let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia].concat());
+    let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), trak].concat());
+
+    [
+        mp4_box(b"ftyp", b"isom\0\0\0\0isomavc1"),
+        mp4_box(b"mdat", &vec![0; mdat_len]),
+        moov,
+    ]
+    .concat()
+}
+
+/// Storage, which keeps objects in memory and counts reads.
+#[derive(Default)]
+pub struct MemoryStorage {
+    pub objects: Mutex<HashMap<String, Vec<u8>>>,
+    pub reads: AtomicUsize,
+}
+
+impl Storage for MemoryStorage {
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<bool, StorageError>> {
+        let exists = self.objects.lock().unwrap().contains_key(key);
+        async move { Ok(exists) }.boxed()
+    }
+
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: &'a [u8],
+        _content_type: &'a str,
+    ) -> BoxFuture<'a, Result<(), StorageError>> {
+        self.objects
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), data.to_vec());
+        async move { Ok(()) }.boxed()
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, StorageError>> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        let data = self.objects.lock().unwrap().get(key).cloned();
+        async move { data.ok_or(StorageError::NotFound) }.boxed()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), StorageError>> {
+        self.objects.lock().unwrap().remove(key);
+        async move { Ok(()) }.boxed()
+    }
+}
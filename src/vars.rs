@@ -13,3 +13,22 @@ pub const ROOM_PATH_MAX_LEN: usize = 32;
 
 pub const ROOM_NAME_MIN_LEN: usize = 2;
 pub const ROOM_NAME_MAX_LEN: usize = 32;
+
+pub const MESSAGE_MIN_LEN: usize = 1;
+pub const MESSAGE_MAX_LEN: usize = 500;
+
+pub const VIDEO_URL_MAX_LEN: usize = 2048;
+pub const VIDEO_TITLE_MAX_LEN: usize = 200;
+
+pub const PLAYLIST_IMPORT_MAX_LEN: usize = 200;
+
+pub const SUBTITLES_MAX_SIZE: usize = 2 * 1024 * 1024;
+pub const SUBTITLES_LABEL_MAX_LEN: usize = 50;
+pub const SUBTITLES_LANGUAGE_MAX_LEN: usize = 35;
+/// Max timing offset of subtitles in milliseconds, either way.
+pub const SUBTITLES_OFFSET_MAX: i32 = 10 * 60 * 1000;
+pub const SUBTITLES_MAX_TRACKS: usize = 20;
+
+pub const EMOTE_NAME_MIN_LEN: usize = 2;
+pub const EMOTE_NAME_MAX_LEN: usize = 32;
+pub const EMOTE_MAX_SIZE: usize = 256 * 1024;